@@ -1,107 +1,81 @@
 use crate::error::AoCError;
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
 use alloc::string::{String, ToString};
-use alloc::vec;
 use alloc::vec::Vec;
+use core::cmp::min;
 use core::num::ParseIntError;
 
+type Mapping = Vec<(u64, u64, u64)>;
+type Graph<'a> = BTreeMap<&'a str, Vec<(&'a str, Mapping)>>;
+type Leg<'a, 'g> = (&'a str, &'a str, &'g Mapping);
+
+struct Almanac<'a> {
+    seeds: Vec<u64>,
+    query: (&'a str, &'a str),
+    mappings: Graph<'a>,
+    inverse: Graph<'a>,
+}
+
 pub(crate) fn part1(input: String) -> Result<String, AoCError> {
-    let mut items = ("", Vec::<u64>::new());
-    let mut mappings = BTreeMap::<&str, (&str, Vec<(u64, u64, u64)>)>::new();
-    input
-        .trim()
-        .split("\n\n")
-        .map(|block| {
-            if block.starts_with("seeds: ") {
-                let nums = block
-                    .trim_start_matches("seeds: ")
-                    .split(' ')
-                    .map(|d| d.parse::<u64>())
-                    .collect::<Result<Vec<u64>, ParseIntError>>()?;
-                items = ("seed", nums);
-            } else {
-                let mut lines = block.lines();
-                let map = lines.next().ok_or(AoCError::from("map name expected"))?;
-                let map = map
-                    .split(' ')
-                    .next()
-                    .ok_or(AoCError::from("map name expected"))?;
-                let mut map = map.split('-');
-                let from = map.next().ok_or(AoCError::from("map from name expected"))?;
-                map.next();
-                let dest = map.next().ok_or(AoCError::from("map dest name expected"))?;
-                let triplets = lines
-                    .map(|line| {
-                        let nums = line.split(' ').map(|d| d.parse::<u64>()).collect::<Result<
-                            Vec<u64>,
-                            ParseIntError,
-                        >>(
-                        )?;
-                        Ok((nums[0], nums[1], nums[2]))
-                    })
-                    .collect::<Result<Vec<(u64, u64, u64)>, AoCError>>()?;
-                mappings.insert(from, (dest, triplets));
-            }
-            Ok(())
-        })
-        .collect::<Result<Vec<()>, AoCError>>()?;
-    progress(&mut items, &mappings, "location")?;
-    let min = items
-        .1
+    let almanac = parse_almanac(&input)?;
+    let items = almanac
+        .seeds
+        .iter()
+        .map(|&seed| (seed, 1))
+        .collect::<Vec<(u64, u64)>>();
+    let (from, to) = almanac.query;
+    let min = almanac
+        .convert(from, to, items)?
         .into_iter()
+        .map(|range| range.0)
         .min()
         .ok_or(AoCError::from("no minimum"))?;
     Ok(min.to_string())
 }
 
-fn progress<'a>(
-    items: &mut (&'a str, Vec<u64>),
-    mappings: &BTreeMap<&str, (&'a str, Vec<(u64, u64, u64)>)>,
-    finish: &str,
-) -> Result<(), AoCError> {
-    while items.0 != finish {
-        let mapping = mappings
-            .get(items.0)
-            .ok_or(AoCError::from("unknown mapping"))?;
-        let new_items = items
-            .1
-            .iter()
-            .map(|item| {
-                let mapping = mapping
-                    .1
-                    .iter()
-                    .find(|mapping| *item >= mapping.1 && *item < mapping.1 + mapping.2);
-                if let Some(mapping) = mapping {
-                    let delta = (mapping.0 as i64) - (mapping.1 as i64);
-                    ((*item as i64) + delta) as u64
-                } else {
-                    *item
-                }
-            })
-            .collect::<Vec<u64>>();
-        *items = (mapping.0, new_items);
+pub(crate) fn part2(input: String) -> Result<String, AoCError> {
+    let almanac = parse_almanac(&input)?;
+    if almanac.seeds.len() % 2 != 0 {
+        return Err(AoCError::from("seed ranges expected"));
     }
-    Ok(())
+    let items = almanac
+        .seeds
+        .chunks_exact(2)
+        .map(|chunk| (chunk[0], chunk[1]))
+        .collect::<Vec<(u64, u64)>>();
+    let (from, to) = almanac.query;
+    let min = almanac
+        .convert(from, to, items)?
+        .into_iter()
+        .map(|range| range.0)
+        .min()
+        .ok_or(AoCError::from("no minimum"))?;
+    Ok(min.to_string())
 }
 
-pub(crate) fn part2(input: String) -> Result<String, AoCError> {
-    let mut items = ("", Vec::<(u64, u64)>::new());
-    let mut mappings = BTreeMap::<&str, (&str, Vec<(u64, u64, u64)>)>::new();
-    let mut mappings_back = BTreeMap::<&str, (&str, Vec<(u64, u64, u64)>)>::new();
+fn parse_almanac(input: &str) -> Result<Almanac<'_>, AoCError> {
+    let mut almanac = Almanac {
+        seeds: Vec::new(),
+        query: ("seed", "location"),
+        mappings: BTreeMap::new(),
+        inverse: BTreeMap::new(),
+    };
     input
         .trim()
         .split("\n\n")
         .map(|block| {
             if block.starts_with("seeds: ") {
-                let nums = block
+                almanac.seeds = block
                     .trim_start_matches("seeds: ")
                     .split(' ')
                     .map(|d| d.parse::<u64>())
                     .collect::<Result<Vec<u64>, ParseIntError>>()?;
-                let mut ranges: Vec<(u64, u64)> = Vec::new();
-                nums.chunks_exact(2)
-                    .for_each(|chunk| ranges.push((chunk[0], chunk[1])));
-                items = ("seed", ranges);
+            } else if block.starts_with("query: ") {
+                let query = block.trim_start_matches("query: ");
+                almanac.query = query
+                    .split_once("-to-")
+                    .ok_or(AoCError::from("query from-to-dest expected"))?;
             } else {
                 let mut lines = block.lines();
                 let map = lines.next().ok_or(AoCError::from("map name expected"))?;
@@ -113,56 +87,224 @@ pub(crate) fn part2(input: String) -> Result<String, AoCError> {
                 let from = map.next().ok_or(AoCError::from("map from name expected"))?;
                 map.next();
                 let dest = map.next().ok_or(AoCError::from("map dest name expected"))?;
-                let triplets = lines
+                let mut triplets = lines
                     .map(|line| {
                         let nums = line.split(' ').map(|d| d.parse::<u64>()).collect::<Result<
                             Vec<u64>,
                             ParseIntError,
                         >>(
                         )?;
-                        Ok((nums[0], nums[1], nums[2]))
+                        match nums[..] {
+                            [dest, source, len] => Ok((dest, source, len)),
+                            _ => Err(AoCError::from("mapping triplet expected")),
+                        }
                     })
-                    .collect::<Result<Vec<(u64, u64, u64)>, AoCError>>()?;
-                let triplets_back = triplets
+                    .collect::<Result<Mapping, AoCError>>()?;
+                triplets.sort_by_key(|t| t.1);
+                validate_mapping(from, dest, &triplets)?;
+                let edges = almanac.mappings.entry(from).or_default();
+                if edges.iter().any(|edge| edge.0 == dest) {
+                    return Err(AoCError::from(format!(
+                        "duplicate {}-to-{} map",
+                        from, dest
+                    )));
+                }
+                let mut inverse = triplets
                     .iter()
-                    .map(|t| (t.1, t.0, t.2))
-                    .collect::<Vec<(u64, u64, u64)>>();
-                mappings.insert(from, (dest, triplets));
-                mappings_back.insert(dest, (from, triplets_back));
+                    .map(|&(dest, source, len)| (source, dest, len))
+                    .collect::<Mapping>();
+                inverse.sort_by_key(|t| t.1);
+                edges.push((dest, triplets));
+                almanac
+                    .inverse
+                    .entry(dest)
+                    .or_default()
+                    .push((from, inverse));
             }
             Ok(())
         })
         .collect::<Result<Vec<()>, AoCError>>()?;
+    almanac.check_cycles()?;
+    Ok(almanac)
+}
 
-    let mut minimum = u64::MAX;
-    mappings
+fn validate_mapping(from: &str, dest: &str, triplets: &Mapping) -> Result<(), AoCError> {
+    if triplets.iter().any(|t| t.2 == 0) {
+        return Err(AoCError::from(format!(
+            "empty range in {}-to-{} map",
+            from, dest
+        )));
+    }
+    if triplets
         .iter()
-        .map(|mapping| {
-            mapping
-                .1
-                 .1
+        .any(|t| t.0.checked_add(t.2).is_none() || t.1.checked_add(t.2).is_none())
+    {
+        return Err(AoCError::from(format!(
+            "range overflow in {}-to-{} map",
+            from, dest
+        )));
+    }
+    if let Some(pair) = triplets
+        .windows(2)
+        .find(|pair| pair[0].1 + pair[0].2 > pair[1].1)
+    {
+        return Err(AoCError::from(format!(
+            "overlapping source ranges {}..{} and {}..{} in {}-to-{} map",
+            pair[0].1,
+            pair[0].1 + pair[0].2,
+            pair[1].1,
+            pair[1].1 + pair[1].2,
+            from,
+            dest
+        )));
+    }
+    Ok(())
+}
+
+impl<'a> Almanac<'a> {
+    fn check_cycles(&self) -> Result<(), AoCError> {
+        let mut done = BTreeSet::<&str>::new();
+        self.mappings.keys().try_for_each(|&category| {
+            let mut path = Vec::new();
+            self.visit(category, &mut path, &mut done)
+        })
+    }
+
+    fn visit(
+        &self,
+        category: &'a str,
+        path: &mut Vec<&'a str>,
+        done: &mut BTreeSet<&'a str>,
+    ) -> Result<(), AoCError> {
+        if done.contains(category) {
+            return Ok(());
+        }
+        if let Some(pos) = path.iter().position(|&c| c == category) {
+            let cycle = path[pos..]
                 .iter()
-                .map(|range| {
-                    let mut point = (mapping.1 .0, vec![range.0]);
-                    progress(&mut point, &mappings, "location")?;
-                    let cur = point.1[0];
+                .chain([&category])
+                .copied()
+                .collect::<Vec<&str>>();
+            return Err(AoCError::from(format!(
+                "cycle in mappings: {}",
+                cycle.join(" -> ")
+            )));
+        }
+        path.push(category);
+        self.mappings
+            .get(category)
+            .into_iter()
+            .flatten()
+            .try_for_each(|edge| self.visit(edge.0, path, done))?;
+        path.pop();
+        done.insert(category);
+        Ok(())
+    }
 
-                    let mut point = (mapping.1 .0, vec![range.0]);
-                    progress(&mut point, &mappings_back, "seed")?;
-                    let back = point.1[0];
-                    if items
-                        .1
-                        .iter()
-                        .any(|range| back >= range.0 && back < range.0 + range.1)
-                        && cur < minimum
-                    {
-                        minimum = cur;
-                    }
-                    Ok(())
-                })
-                .collect::<Result<Vec<()>, AoCError>>()?;
-            Ok(())
+    fn count_paths(
+        graph: &Graph<'a>,
+        from: &'a str,
+        to: &str,
+        counts: &mut BTreeMap<&'a str, usize>,
+    ) -> usize {
+        if from == to {
+            return 1;
+        }
+        if let Some(&count) = counts.get(from) {
+            return count;
+        }
+        let count = graph
+            .get(from)
+            .into_iter()
+            .flatten()
+            .fold(0, |count, edge| {
+                min(2, count + Self::count_paths(graph, edge.0, to, counts))
+            });
+        counts.insert(from, count);
+        count
+    }
+
+    fn route<'g>(
+        graph: &'g Graph<'a>,
+        from: &'a str,
+        to: &str,
+    ) -> Result<Option<Vec<Leg<'a, 'g>>>, AoCError> {
+        let mut counts = BTreeMap::new();
+        match Self::count_paths(graph, from, to, &mut counts) {
+            0 => return Ok(None),
+            1 => {}
+            _ => {
+                return Err(AoCError::from(format!(
+                    "ambiguous mapping from {} to {}",
+                    from, to
+                )))
+            }
+        }
+        let mut path = Vec::new();
+        let mut category = from;
+        while category != to {
+            let (dest, mapping) = graph
+                .get(category)
+                .into_iter()
+                .flatten()
+                .find(|edge| edge.0 == to || counts.get(edge.0) == Some(&1))
+                .ok_or(AoCError::from("path lost"))?;
+            path.push((category, *dest, mapping));
+            category = dest;
+        }
+        Ok(Some(path))
+    }
+
+    fn convert(
+        &self,
+        from: &'a str,
+        to: &str,
+        items: Vec<(u64, u64)>,
+    ) -> Result<Vec<(u64, u64)>, AoCError> {
+        let path = match Self::route(&self.mappings, from, to)? {
+            Some(path) => path,
+            None => {
+                let path = Self::route(&self.inverse, from, to)?.ok_or(AoCError::from(format!(
+                    "no mapping from {} to {}",
+                    from, to
+                )))?;
+                path.iter()
+                    .try_for_each(|&(from, dest, mapping)| validate_mapping(from, dest, mapping))?;
+                path
+            }
+        };
+        Ok(path.into_iter().fold(items, |items, (_, _, mapping)| {
+            apply_mapping(mapping, items)
+        }))
+    }
+}
+
+fn apply_mapping(mapping: &Mapping, items: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    items
+        .into_iter()
+        .filter(|&(_, len)| len > 0)
+        .flat_map(|(mut start, len)| {
+            let end = start.saturating_add(len);
+            let mut ranges = Vec::new();
+            for &(dest, source, len) in mapping {
+                if source + len <= start {
+                    continue;
+                }
+                if source >= end {
+                    break;
+                }
+                if source > start {
+                    ranges.push((start, source - start));
+                    start = source;
+                }
+                let stop = min(end, source + len);
+                ranges.push((dest + (start - source), stop - start));
+                start = stop;
+            }
+            if start < end {
+                ranges.push((start, end - start));
+            }
+            ranges
         })
-        .collect::<Result<Vec<()>, AoCError>>()?;
-    Ok(minimum.to_string())
+        .collect()
 }
@@ -1,61 +1,104 @@
 use crate::error::AoCError;
+use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
+use bnum::cast::As;
+use bnum::types::{U256, U512};
 
 pub(crate) fn part1(input: String) -> Result<String, AoCError> {
+    let ans = parse_races(&input)?
+        .into_iter()
+        .map(|(time, distance)| count(solve(time, distance)))
+        .try_fold(U512::ONE, |a, c| a.checked_mul(c))
+        .ok_or(AoCError::from("overflow"))?;
+    Ok(ans.to_string())
+}
+
+pub(crate) fn part2(input: String) -> Result<String, AoCError> {
+    let (time, distance) = parse_race(&input)?;
+    Ok(count(solve(time, distance)).to_string())
+}
+
+pub(crate) fn details(input: String) -> Result<String, AoCError> {
+    let races = parse_races(&input)?;
+    let race = parse_race(&input)?;
+    Ok(races
+        .into_iter()
+        .enumerate()
+        .map(|(i, race)| (format!("Race {}", i + 1), race))
+        .chain([(String::from("Single race"), race)])
+        .map(|(name, (time, distance))| match solve(time, distance) {
+            Some((first, last)) => format!(
+                "{}: hold {}..={} ms ({} ways)",
+                name,
+                first,
+                last,
+                count(Some((first, last)))
+            ),
+            None => format!("{}: no way to win", name),
+        })
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+fn parse_races(input: &str) -> Result<Vec<(U512, U512)>, AoCError> {
     let mut lines = input.trim().lines();
     let times = lines.next().ok_or(AoCError::from("no times line"))?;
     let distances = lines.next().ok_or(AoCError::from("no distances line"))?;
     let times = times
         .split(' ')
-        .filter_map(|d| d.parse::<u64>().ok())
-        .collect::<Vec<u64>>();
+        .filter_map(|d| d.parse::<U256>().ok())
+        .map(|d| d.as_())
+        .collect::<Vec<U512>>();
     let distances = distances
         .split(' ')
-        .filter_map(|d| d.parse::<u64>().ok())
-        .collect::<Vec<u64>>();
-    let ans = times
-        .into_iter()
-        .zip(distances)
-        .map(|pair| solve(pair.0, pair.1))
-        .product::<u64>();
-    Ok(ans.to_string())
+        .filter_map(|d| d.parse::<U256>().ok())
+        .map(|d| d.as_())
+        .collect::<Vec<U512>>();
+    Ok(times.into_iter().zip(distances).collect())
 }
 
-pub(crate) fn part2(input: String) -> Result<String, AoCError> {
+fn parse_race(input: &str) -> Result<(U512, U512), AoCError> {
     let input = input.trim().replace(' ', "");
     let mut lines = input.lines();
     let times = lines.next().ok_or(AoCError::from("no time line"))?;
     let distances = lines.next().ok_or(AoCError::from("no distances line"))?;
-    let time = times.trim_start_matches("Time:").parse::<u64>()?;
-    let distance = distances.trim_start_matches("Distance:").parse::<u64>()?;
-    Ok(solve(time, distance).to_string())
-}
-
-fn solve(time: u64, distance: u64) -> u64 {
-    let tip = binary_search(0, time, |t| {
-        let x = calc_distance(t, time);
-        let y = calc_distance(t + 1, time);
-        x > y
-    });
-    let left = binary_search(0, tip, |t| calc_distance(t, time) > distance);
-    let right = binary_search(tip, time, |t| calc_distance(t, time) <= distance);
-    right - left
-}
-
-fn calc_distance(button: u64, time: u64) -> u64 {
-    button * (time - button)
-}
-
-fn binary_search<F: Fn(u64) -> bool>(n: u64, m: u64, f: F) -> u64 {
-    let (mut i, mut j) = (n, m);
-    while i < j {
-        let h = (i + j) / 2;
-        if !f(h) {
-            i = h + 1
-        } else {
-            j = h
+    let time = times.trim_start_matches("Time:").parse::<U256>()?;
+    let distance = distances.trim_start_matches("Distance:").parse::<U256>()?;
+    Ok((time.as_(), distance.as_()))
+}
+
+fn solve(time: U512, distance: U512) -> Option<(U512, U512)> {
+    let wins = |button: U512| button * (time - button) > distance;
+    let tip = time / U512::TWO;
+    if !wins(tip) {
+        return None;
+    }
+    let discriminant = time * time - U512::FOUR * distance;
+    let mut first = (time - isqrt(discriminant)) / U512::TWO;
+    while !wins(first) {
+        first += U512::ONE;
+    }
+    while first > U512::ZERO && wins(first - U512::ONE) {
+        first -= U512::ONE;
+    }
+    Some((first, time - first))
+}
+
+fn count(interval: Option<(U512, U512)>) -> U512 {
+    interval.map_or(U512::ZERO, |(first, last)| last - first + U512::ONE)
+}
+
+fn isqrt(n: U512) -> U512 {
+    if n < U512::TWO {
+        return n;
+    }
+    let mut x = U512::ONE << n.bits().div_ceil(2);
+    loop {
+        let y = (x + n / x) / U512::TWO;
+        if y >= x {
+            return x;
         }
+        x = y;
     }
-    i
 }
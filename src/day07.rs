@@ -1,40 +1,50 @@
 use crate::error::AoCError;
+use crate::utils::parse_options;
 use alloc::collections::BTreeMap;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
-use core::cmp::Ordering;
-use core::ops::Range;
+
+const MAX_HAND: usize = 16;
+
+struct HandRules {
+    ranks: [u8; 128],
+    wild: [bool; 128],
+    length: usize,
+    categories: Vec<([u8; MAX_HAND], usize)>,
+}
 
 pub(crate) fn part1(input: String) -> Result<String, AoCError> {
-    solve(
-        input,
-        [
-            '2', '3', '4', '5', '6', '7', '8', '9', 'T', 'J', 'Q', 'K', 'A',
-        ],
-        9..10,
-    )
+    let (options, hands) =
+        parse_options(&input, &["cards", "wild", "jokers", "length", "categories"])?;
+    let cards = options.get("cards").copied().unwrap_or("23456789TJQKA");
+    let wild = options.get("wild").copied().unwrap_or("");
+    let rules = HandRules::new(cards, wild, &options)?;
+    solve(hands, &rules)
 }
 
 pub(crate) fn part2(input: String) -> Result<String, AoCError> {
-    solve(
-        input,
-        [
-            'J', '2', '3', '4', '5', '6', '7', '8', '9', 'T', 'Q', 'K', 'A',
-        ],
-        1..13,
-    )
+    let (options, hands) =
+        parse_options(&input, &["cards", "wild", "jokers", "length", "categories"])?;
+    let cards = options.get("cards").copied().unwrap_or("23456789TJQKA");
+    let jokers = options.get("jokers").copied().unwrap_or("J");
+    let cards = jokers
+        .chars()
+        .chain(cards.chars().filter(|c| !jokers.contains(*c)))
+        .collect::<String>();
+    let wild = jokers
+        .chars()
+        .chain(options.get("wild").copied().unwrap_or("").chars())
+        .collect::<String>();
+    let rules = HandRules::new(&cards, &wild, &options)?;
+    solve(hands, &rules)
 }
 
-fn solve(input: String, cards: [char; 13], replacements: Range<usize>) -> Result<String, AoCError> {
-    let mut hands = parse_hands(&input)?;
-    hands.sort_by(|a, b| {
-        let ord = make_strongest(a.0, &cards[replacements.clone()])
-            .cmp(&make_strongest(b.0, &cards[replacements.clone()]));
-        if ord != Ordering::Equal {
-            return ord;
-        }
-        cmp_cards(a.0, b.0, cards)
-    });
+fn solve(input: &str, rules: &HandRules) -> Result<String, AoCError> {
+    let mut hands = parse_hands(input)?
+        .into_iter()
+        .map(|(hand, bid)| Ok((rules.key(hand)?, bid)))
+        .collect::<Result<Vec<_>, AoCError>>()?;
+    hands.sort_unstable_by_key(|hand| hand.0);
     let s = hands
         .iter()
         .enumerate()
@@ -55,48 +65,130 @@ fn parse_hands(input: &str) -> Result<Vec<(&str, u64)>, AoCError> {
         .collect::<Result<Vec<(&str, u64)>, AoCError>>()
 }
 
-fn cmp_cards(a: &str, b: &str, cards: [char; 13]) -> Ordering {
-    a.chars()
-        .zip(b.chars())
-        .map(|(a, b)| {
-            let pa = cards
-                .iter()
-                .position(|m| a == *m)
-                .map(|m| m + 1)
-                .unwrap_or(0);
-            let pb = cards
-                .iter()
-                .position(|m| b == *m)
-                .map(|m| m + 1)
-                .unwrap_or(0);
-            pa.cmp(&pb)
+impl HandRules {
+    fn new(cards: &str, wild: &str, options: &BTreeMap<&str, &str>) -> Result<Self, AoCError> {
+        let length = options
+            .get("length")
+            .map_or(Ok(5), |length| length.parse::<usize>())?;
+        if length == 0 || length > MAX_HAND {
+            return Err(AoCError::from("unsupported hand length"));
+        }
+        let mut ranks = [0u8; 128];
+        for (rank, card) in cards.chars().enumerate() {
+            let slot = ranks
+                .get_mut(card as usize)
+                .ok_or(AoCError::from("non-ascii card"))?;
+            if *slot != 0 {
+                return Err(AoCError::from("duplicate card"));
+            }
+            *slot = u8::try_from(rank + 1)?;
+        }
+        let mut wildcards = [false; 128];
+        for card in wild.chars() {
+            if ranks.get(card as usize).copied().unwrap_or(0) == 0 {
+                return Err(AoCError::from("unknown wildcard"));
+            }
+            wildcards[card as usize] = true;
+        }
+        let categories = match options.get("categories") {
+            Some(categories) => categories
+                .split(' ')
+                .map(|category| {
+                    let mut pattern = [0u8; MAX_HAND];
+                    let mut len = 0;
+                    for count in category.split(',') {
+                        *pattern
+                            .get_mut(len)
+                            .ok_or(AoCError::from("category too long"))? = count.parse::<u8>()?;
+                        len += 1;
+                    }
+                    pattern[..len].sort_unstable_by(|a, b| b.cmp(a));
+                    if len == 0
+                        || pattern[len - 1] == 0
+                        || pattern[..len].iter().map(|&c| c as usize).sum::<usize>() != length
+                    {
+                        return Err(AoCError::from("invalid category"));
+                    }
+                    Ok((pattern, len))
+                })
+                .collect::<Result<Vec<_>, AoCError>>()?,
+            None => {
+                let mut categories = Vec::new();
+                partitions(length, length, &mut [0u8; MAX_HAND], 0, &mut categories);
+                categories.sort_unstable();
+                categories
+            }
+        };
+        Ok(HandRules {
+            ranks,
+            wild: wildcards,
+            length,
+            categories,
         })
-        .find(|p| *p != Ordering::Equal)
-        .unwrap_or(Ordering::Equal)
-}
+    }
 
-fn hand_type(hand: &str) -> usize {
-    let mut counts: BTreeMap<char, usize> = BTreeMap::new();
-    hand.chars()
-        .for_each(|c| *counts.entry(c).or_insert(0) += 1);
-    let mut counts = counts.into_values().collect::<Vec<usize>>();
-    counts.sort();
-    match &counts[..] {
-        &[5] => 7,
-        &[1, 4] => 6,
-        &[2, 3] => 5,
-        &[1, 1, 3] => 4,
-        &[1, 2, 2] => 3,
-        &[1, 1, 1, 2] => 2,
-        &[1, 1, 1, 1, 1] => 1,
-        _ => 0,
+    fn classify(&self, hand: &[u8]) -> Option<usize> {
+        let mut counts = [0u8; 128];
+        let mut wild = 0;
+        hand.iter().for_each(|&card| {
+            if self.wild[card as usize] {
+                wild += 1;
+            } else {
+                counts[card as usize] += 1;
+            }
+        });
+        let mut groups = [0u8; MAX_HAND];
+        let mut len = 0;
+        hand.iter().for_each(|&card| {
+            if counts[card as usize] > 0 {
+                groups[len] = counts[card as usize];
+                counts[card as usize] = 0;
+                len += 1;
+            }
+        });
+        groups[..len].sort_unstable_by(|a, b| b.cmp(a));
+        debug_assert_eq!(
+            groups[..len].iter().map(|&c| c as usize).sum::<usize>() + wild,
+            self.length
+        );
+        self.categories.iter().rposition(|&(pattern, size)| {
+            len <= size && groups[..len].iter().zip(pattern).all(|(g, p)| *g <= p)
+        })
+    }
+
+    fn key(&self, hand: &str) -> Result<(usize, [u8; MAX_HAND]), AoCError> {
+        let hand = hand.as_bytes();
+        if hand.len() != self.length {
+            return Err(AoCError::from("invalid hand length"));
+        }
+        let mut strength = [0u8; MAX_HAND];
+        for (slot, &card) in strength.iter_mut().zip(hand) {
+            *slot = self.ranks.get(card as usize).copied().unwrap_or(0);
+            if *slot == 0 {
+                return Err(AoCError::from("unknown card"));
+            }
+        }
+        let category = self
+            .classify(hand)
+            .ok_or(AoCError::from("hand matches no category"))?;
+        Ok((category, strength))
     }
 }
 
-fn make_strongest(hand: &str, cards: &[char]) -> usize {
-    cards
-        .iter()
-        .map(|card| hand_type(hand.replace('J', card.to_string().as_str()).as_str()))
-        .max()
-        .unwrap_or(0)
+fn partitions(
+    n: usize,
+    max: usize,
+    pattern: &mut [u8; MAX_HAND],
+    len: usize,
+    categories: &mut Vec<([u8; MAX_HAND], usize)>,
+) {
+    if n == 0 {
+        categories.push((*pattern, len));
+        return;
+    }
+    for part in (1..=max.min(n)).rev() {
+        pattern[len] = part as u8;
+        partitions(n - part, part, pattern, len + 1, categories);
+    }
+    pattern[len] = 0;
 }
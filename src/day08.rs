@@ -1,18 +1,91 @@
 use crate::error::AoCError;
-use crate::utils::lcm;
+use crate::utils::parse_options;
 use alloc::collections::BTreeMap;
+use alloc::format;
 use alloc::string::{String, ToString};
+use alloc::vec;
 use alloc::vec::Vec;
+use bnum::types::I512;
+
+struct Network<'a> {
+    names: Vec<&'a str>,
+    left: Vec<u32>,
+    right: Vec<u32>,
+    steps: Vec<bool>,
+    lifts: Vec<Vec<u32>>,
+}
+
+struct Walk {
+    prefix: usize,
+    cycle: usize,
+    hits: Vec<usize>,
+}
 
 pub(crate) fn part1(input: String) -> Result<String, AoCError> {
-    solve(input, |n| **n == "AAA")
+    solve(input, |n| n == "AAA", |n| n == "ZZZ")
 }
 
 pub(crate) fn part2(input: String) -> Result<String, AoCError> {
-    solve(input, |n| n.ends_with('A'))
+    solve(input, |n| n.ends_with('A'), |n| n.ends_with('Z'))
+}
+
+pub(crate) fn details(input: String) -> Result<String, AoCError> {
+    let (options, input) = parse_options(&input, &["walk"])?;
+    let mut network = parse_network(input)?;
+    let finishing = network.mark(|n| n.ends_with('Z'));
+    let starts = network.mark(|n| n.ends_with('A'));
+    let mut lines = starts
+        .iter()
+        .enumerate()
+        .filter(|(_, &start)| start)
+        .map(|(start, _)| {
+            let walk = network.analyze(start as u32, &finishing);
+            format!(
+                "{}: prefix {}, cycle {}, finishes at {:?}",
+                network.names[start], walk.prefix, walk.cycle, walk.hits
+            )
+        })
+        .collect::<Vec<String>>();
+    if let Some(walk) = options.get("walk") {
+        let walk = walk.parse::<u64>()?;
+        network.build_lifts(walk / network.steps.len() as u64);
+        starts
+            .iter()
+            .enumerate()
+            .filter(|(_, &start)| start)
+            .for_each(|(start, _)| {
+                let node = network.walk(start as u32, walk);
+                lines.push(format!(
+                    "{} -> {} after {} steps",
+                    network.names[start], network.names[node as usize], walk
+                ));
+            });
+    }
+    Ok(lines.join("\n"))
+}
+
+fn solve<F: Fn(&str) -> bool, G: Fn(&str) -> bool>(
+    input: String,
+    starting: F,
+    finishing: G,
+) -> Result<String, AoCError> {
+    let (_, input) = parse_options(&input, &["walk"])?;
+    let network = parse_network(input)?;
+    let finishing = network.mark(finishing);
+    let walks = network
+        .mark(starting)
+        .into_iter()
+        .enumerate()
+        .filter(|(_, start)| *start)
+        .map(|(start, _)| network.analyze(start as u32, &finishing))
+        .collect::<Vec<Walk>>();
+    if walks.is_empty() {
+        return Err(AoCError::from("no start"));
+    }
+    Ok(combine(&walks)?.to_string())
 }
 
-fn solve<F: Fn(&&&str) -> bool>(input: String, starting: F) -> Result<String, AoCError> {
+fn parse_network(input: &str) -> Result<Network<'_>, AoCError> {
     let (steps, nodes) = input
         .trim()
         .split_once("\n\n")
@@ -30,31 +103,169 @@ fn solve<F: Fn(&&&str) -> bool>(input: String, starting: F) -> Result<String, Ao
             Ok((from, (left, right)))
         })
         .collect::<Result<BTreeMap<&str, (&str, &str)>, AoCError>>()?;
-    let steps = steps.as_bytes();
-
-    let mut finish_times = BTreeMap::<&str, usize>::new();
-    let mut cur_nodes = nodes.keys().filter(starting).collect::<Vec<&&str>>();
-    let mut cur_step = 0;
-    while finish_times.len() != cur_nodes.len() {
-        cur_nodes.iter().filter(|n| n.ends_with('Z')).for_each(|n| {
-            finish_times.entry(n).or_insert(cur_step);
-        });
-        cur_nodes = cur_nodes
+    let steps = steps
+        .bytes()
+        .map(|step| match step {
+            b'L' => Ok(false),
+            b'R' => Ok(true),
+            _ => Err(AoCError::from("unknown instruction")),
+        })
+        .collect::<Result<Vec<bool>, AoCError>>()?;
+    if steps.is_empty() {
+        return Err(AoCError::from("no steps"));
+    }
+
+    let ids = nodes
+        .keys()
+        .enumerate()
+        .map(|(id, &name)| (name, id as u32))
+        .collect::<BTreeMap<&str, u32>>();
+    let id = |name| ids.get(name).copied().ok_or(AoCError::from("unknown node"));
+    let (left, right) = nodes
+        .values()
+        .map(|(left, right)| Ok((id(left)?, id(right)?)))
+        .collect::<Result<(Vec<u32>, Vec<u32>), AoCError>>()?;
+    Ok(Network {
+        names: nodes.into_keys().collect(),
+        left,
+        right,
+        steps,
+        lifts: Vec::new(),
+    })
+}
+
+impl<'a> Network<'a> {
+    fn mark<F: Fn(&str) -> bool>(&self, f: F) -> Vec<bool> {
+        self.names.iter().map(|name| f(name)).collect()
+    }
+
+    fn step(&self, node: u32, cur_step: usize) -> u32 {
+        if self.steps[cur_step] {
+            self.right[node as usize]
+        } else {
+            self.left[node as usize]
+        }
+    }
+
+    fn analyze(&self, start: u32, finishing: &[bool]) -> Walk {
+        let width = self.steps.len();
+        let mut seen = vec![usize::MAX; self.names.len() * width];
+        let mut hits = Vec::new();
+        let mut node = start;
+        let mut cur_step = 0;
+        loop {
+            let state = node as usize * width + cur_step % width;
+            if seen[state] != usize::MAX {
+                return Walk {
+                    prefix: seen[state],
+                    cycle: cur_step - seen[state],
+                    hits,
+                };
+            }
+            seen[state] = cur_step;
+            if finishing[node as usize] {
+                hits.push(cur_step);
+            }
+            node = self.step(node, cur_step % width);
+            cur_step += 1;
+        }
+    }
+
+    fn build_lifts(&mut self, passes: u64) {
+        let pass = (0..self.names.len() as u32)
+            .map(|node| (0..self.steps.len()).fold(node, |node, i| self.step(node, i)))
+            .collect::<Vec<u32>>();
+        self.lifts = Vec::from([pass]);
+        while passes
+            .checked_shr(self.lifts.len() as u32)
+            .is_some_and(|p| p > 0)
+        {
+            let last = &self.lifts[self.lifts.len() - 1];
+            let next = last.iter().map(|&node| last[node as usize]).collect();
+            self.lifts.push(next);
+        }
+    }
+
+    fn walk(&self, start: u32, steps: u64) -> u32 {
+        let width = self.steps.len() as u64;
+        let passes = steps / width;
+        let node = self
+            .lifts
+            .iter()
+            .enumerate()
+            .filter(|(level, _)| passes >> level & 1 == 1)
+            .fold(start, |node, (_, lift)| lift[node as usize]);
+        (0..(steps % width) as usize).fold(node, |node, i| self.step(node, i))
+    }
+}
+
+impl Walk {
+    fn finished_at(&self, time: usize) -> bool {
+        let time = if time < self.prefix {
+            time
+        } else {
+            (time - self.prefix) % self.cycle + self.prefix
+        };
+        self.hits.binary_search(&time).is_ok()
+    }
+
+    fn cycle_hits(&self) -> impl Iterator<Item = usize> + '_ {
+        self.hits
+            .iter()
+            .copied()
+            .filter(|&hit| hit >= self.prefix)
+            .map(|hit| hit % self.cycle)
+    }
+}
+
+fn combine(walks: &[Walk]) -> Result<I512, AoCError> {
+    let settled = walks.iter().map(|w| w.prefix).max().unwrap_or(0);
+    if let Some(time) = (0..settled).find(|&t| walks.iter().all(|w| w.finished_at(t))) {
+        return Ok(I512::from(time as u64));
+    }
+    let mut congruences = Vec::from([(I512::ZERO, I512::ONE)]);
+    for walk in walks {
+        let cycle = I512::from(walk.cycle as u64);
+        congruences = congruences
             .iter()
-            .map(|cur_node| {
-                let step = nodes.get(*cur_node).ok_or(AoCError::from("no step"))?;
-                if steps[cur_step % steps.len()] == ('L' as u8) {
-                    Ok(&step.0)
-                } else {
-                    Ok(&step.1)
-                }
+            .flat_map(|&c| {
+                walk.cycle_hits()
+                    .filter_map(move |hit| crt(c, (I512::from(hit as u64), cycle)))
             })
-            .collect::<Result<Vec<&&str>, AoCError>>()?;
-        cur_step += 1;
-    }
-    Ok(finish_times
-        .into_values()
-        .map(|v| v as u64)
-        .fold(1, lcm)
-        .to_string())
+            .collect();
+        congruences.sort();
+        congruences.dedup();
+    }
+    let settled = I512::from(settled as u64);
+    congruences
+        .into_iter()
+        .map(|(r, m)| {
+            if r >= settled {
+                r
+            } else {
+                r + (settled - r + m - I512::ONE) / m * m
+            }
+        })
+        .min()
+        .ok_or(AoCError::from("never finishes"))
+}
+
+fn crt((r1, m1): (I512, I512), (r2, m2): (I512, I512)) -> Option<(I512, I512)> {
+    let (g, p, _) = extended_gcd(m1, m2);
+    if (r2 - r1) % g != I512::ZERO {
+        return None;
+    }
+    let m = m1 / g * m2;
+    let k = ((r2 - r1) / g * p) % (m2 / g);
+    let r = (r1 + m1 * k) % m;
+    Some((if r < I512::ZERO { r + m } else { r }, m))
+}
+
+fn extended_gcd(a: I512, b: I512) -> (I512, I512, I512) {
+    if b == I512::ZERO {
+        (a, I512::ONE, I512::ZERO)
+    } else {
+        let (g, x, y) = extended_gcd(b, a % b);
+        (g, y, x - a / b * y)
+    }
 }
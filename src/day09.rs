@@ -1,21 +1,27 @@
 use crate::error::AoCError;
+use crate::utils::parse_options;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
+use bnum::types::I1024;
 use core::num::ParseIntError;
 
 pub(crate) fn part1(input: String) -> Result<String, AoCError> {
-    solve(input, &|n, v| n + v, &|v| v.iter().next_back().copied())
+    let (options, input) = parse_options(&input, &["forward", "backward"])?;
+    let forward = options.get("forward").map_or(Ok(1), |v| v.parse::<i64>())?;
+    solve(input, |len| {
+        I1024::from(len as u64) - I1024::ONE + I1024::from(forward)
+    })
 }
 
 pub(crate) fn part2(input: String) -> Result<String, AoCError> {
-    solve(input, &|n, v| v - n, &|v| v.iter().next().copied())
+    let (options, input) = parse_options(&input, &["forward", "backward"])?;
+    let backward = options
+        .get("backward")
+        .map_or(Ok(1), |v| v.parse::<i64>())?;
+    solve(input, |_| -I1024::from(backward))
 }
 
-fn solve<F: Fn(i64, i64) -> i64, G: Fn(&[i64]) -> Option<i64>>(
-    input: String,
-    f: &F,
-    getter: &G,
-) -> Result<String, AoCError> {
+fn solve<F: Fn(usize) -> I1024>(input: &str, index: F) -> Result<String, AoCError> {
     let predictions = input
         .trim()
         .lines()
@@ -24,23 +30,41 @@ fn solve<F: Fn(i64, i64) -> i64, G: Fn(&[i64]) -> Option<i64>>(
                 .split(' ')
                 .map(|v| v.parse::<i64>())
                 .collect::<Result<Vec<i64>, ParseIntError>>()?;
-            Ok(predict(values, f, getter)?)
+            predict(&newton(&values)?, index(values.len()))
         })
-        .collect::<Result<Vec<i64>, AoCError>>()?;
-    Ok(predictions.into_iter().sum::<i64>().to_string())
+        .collect::<Result<Vec<I1024>, AoCError>>()?;
+    Ok(predictions.into_iter().sum::<I1024>().to_string())
 }
 
-fn predict<F: Fn(i64, i64) -> i64, G: Fn(&[i64]) -> Option<i64>>(
-    values: Vec<i64>,
-    f: &F,
-    getter: &G,
-) -> Result<i64, AoCError> {
-    if values.iter().all(|v| *v == 0) {
-        Ok(0)
-    } else {
-        let diffs = values.windows(2).map(|v| v[1] - v[0]).collect::<Vec<i64>>();
-        let v = getter(values.as_slice()).ok_or(AoCError::from("empty list"))?;
-        let n = predict(diffs, f, getter)?;
-        Ok(f(n, v))
+fn newton(values: &[i64]) -> Result<Vec<I1024>, AoCError> {
+    let mut row = values
+        .iter()
+        .map(|&v| I1024::from(v))
+        .collect::<Vec<I1024>>();
+    let mut coefficients = Vec::new();
+    while !row.is_empty() {
+        if row.iter().all(|v| *v == I1024::ZERO) {
+            return Ok(coefficients);
+        }
+        coefficients.push(row[0]);
+        row = row.windows(2).map(|v| v[1] - v[0]).collect();
     }
+    Err(AoCError::from("differences never reach zero"))
+}
+
+fn predict(coefficients: &[I1024], index: I1024) -> Result<I1024, AoCError> {
+    let mut binomial = I1024::ONE;
+    let mut value = I1024::ZERO;
+    for (j, coefficient) in coefficients.iter().enumerate() {
+        let j = I1024::from(j as u64);
+        value = binomial
+            .checked_mul(*coefficient)
+            .and_then(|term| value.checked_add(term))
+            .ok_or(AoCError::from("overflow"))?;
+        binomial = binomial
+            .checked_mul(index - j)
+            .ok_or(AoCError::from("overflow"))?
+            / (j + I1024::ONE);
+    }
+    Ok(value)
 }
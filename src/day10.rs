@@ -1,282 +1,220 @@
-use crate::day10::Direction::{East, North, South, West};
 use crate::error::AoCError;
-use alloc::collections::BTreeSet;
+use crate::utils::parse_options;
+use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 
-#[derive(Copy, Clone)]
-enum Direction {
-    North,
-    South,
-    West,
-    East,
+const NORTH: u8 = 1;
+const SOUTH: u8 = 2;
+const WEST: u8 = 4;
+const EAST: u8 = 8;
+
+const DIRECTIONS: [(u8, isize, isize); 4] =
+    [(NORTH, -1, 0), (SOUTH, 1, 0), (WEST, 0, -1), (EAST, 0, 1)];
+
+const PIPES: [(&str, u8); 6] = [
+    ("|│┃", NORTH | SOUTH),
+    ("-─━", WEST | EAST),
+    ("L└┗╰", NORTH | EAST),
+    ("J┘┛╯", NORTH | WEST),
+    ("7┐┓╮", SOUTH | WEST),
+    ("F┌┏╭", SOUTH | EAST),
+];
+
+struct Maze {
+    tiles: Vec<Vec<u8>>,
+    start: (usize, usize),
 }
 
 pub(crate) fn part1(input: String) -> Result<String, AoCError> {
-    let map = input
-        .trim()
-        .lines()
-        .map(|line| line.chars().collect::<Vec<char>>())
-        .collect::<Vec<Vec<char>>>();
-    let (start_row, start_column) = map
+    let mut maze = parse_maze(&input)?;
+    let path = maze.find_loop()?;
+    Ok((path.len() >> 1).to_string())
+}
+
+pub(crate) fn part2(input: String) -> Result<String, AoCError> {
+    let mut maze = parse_maze(&input)?;
+    let path = maze.find_loop()?;
+    let enclosed = shoelace(&path);
+    let inside = scanline(&maze.tiles, &path);
+    if enclosed != inside.iter().flatten().filter(|&&v| v).count() {
+        return Err(AoCError::from("shoelace and scanline disagree"));
+    }
+    Ok(enclosed.to_string())
+}
+
+pub(crate) fn details(input: String) -> Result<String, AoCError> {
+    let mut maze = parse_maze(&input)?;
+    let path = maze.find_loop()?;
+    let inside = scanline(&maze.tiles, &path);
+    let path = path.into_iter().collect::<BTreeSet<(usize, usize)>>();
+    Ok(maze
+        .tiles
         .iter()
+        .zip(inside)
         .enumerate()
-        .find_map(|(row, line)| {
-            if let Some(col) = line.iter().position(|c| *c == 'S') {
-                Some((row, col))
-            } else {
-                None
-            }
-        })
-        .ok_or(AoCError::from("no start"))?;
-    let (_, longest) = ['|', '-', 'L', 'J', '7', 'F']
-        .into_iter()
-        .flat_map(|start_tile| {
-            let map = &map;
-            [North, South, East, West]
-                .into_iter()
-                .filter_map(move |start_direction| {
-                    run_map(map, start_tile, start_direction, start_row, start_column)
+        .map(|(row, (line, inside))| {
+            line.iter()
+                .zip(inside)
+                .enumerate()
+                .map(|(column, (&tile, inside))| {
+                    if path.contains(&(row, column)) {
+                        PIPES
+                            .iter()
+                            .find(|pipe| pipe.1 == tile)
+                            .and_then(|pipe| pipe.0.chars().nth(1))
+                            .unwrap_or('?')
+                    } else if inside {
+                        'I'
+                    } else if tile != 0 {
+                        '.'
+                    } else {
+                        'O'
+                    }
                 })
+                .collect::<String>()
         })
-        .max()
-        .ok_or(AoCError::from("no loop"))?;
-    Ok((longest.len() >> 1).to_string())
+        .collect::<Vec<String>>()
+        .join("\n"))
 }
 
-pub(crate) fn part2(input: String) -> Result<String, AoCError> {
-    let mut map = input
+fn parse_maze(input: &str) -> Result<Maze, AoCError> {
+    let (options, input) = parse_options(input, &["tiles", "start"])?;
+    let pipes = match options.get("tiles") {
+        Some(tiles) => {
+            if tiles.chars().count() != PIPES.len() {
+                return Err(AoCError::from("six pipe tiles expected"));
+            }
+            tiles
+                .chars()
+                .zip(PIPES)
+                .map(|(tile, (_, mask))| (tile, mask))
+                .collect::<BTreeMap<char, u8>>()
+        }
+        None => PIPES
+            .into_iter()
+            .flat_map(|(tiles, mask)| tiles.chars().map(move |tile| (tile, mask)))
+            .collect::<BTreeMap<char, u8>>(),
+    };
+    let start_tile = match options.get("start") {
+        Some(start) => start
+            .chars()
+            .next()
+            .ok_or(AoCError::from("no start tile"))?,
+        None => 'S',
+    };
+
+    let mut start = None;
+    let tiles = input
         .trim()
         .lines()
-        .map(|line| line.chars().collect::<Vec<char>>())
-        .collect::<Vec<Vec<char>>>();
-    let (start_row, start_column) = map
-        .iter()
         .enumerate()
-        .find_map(|(row, line)| {
-            if let Some(col) = line.iter().position(|c| *c == 'S') {
-                Some((row, col))
-            } else {
-                None
-            }
+        .map(|(row, line)| {
+            line.chars()
+                .enumerate()
+                .map(|(column, tile)| {
+                    if tile == start_tile {
+                        start = Some((row, column));
+                    }
+                    pipes.get(&tile).copied().unwrap_or(0)
+                })
+                .collect::<Vec<u8>>()
         })
-        .ok_or(AoCError::from("no start"))?;
-    let (start_tile, longest) = [
-        ('|', [North, South]),
-        ('-', [East, West]),
-        ('L', [South, West]),
-        ('J', [South, East]),
-        ('7', [North, East]),
-        ('F', [North, West]),
-    ]
-    .into_iter()
-    .filter_map(|(start_tile, directions)| {
-        run_map(&map, start_tile, directions[0], start_row, start_column)
-            .and_then(|_| run_map(&map, start_tile, directions[1], start_row, start_column))
+        .collect::<Vec<Vec<u8>>>();
+    Ok(Maze {
+        tiles,
+        start: start.ok_or(AoCError::from("no start"))?,
     })
-    .max_by_key(|v| v.1.len())
-    .ok_or(AoCError::from("no loop"))?;
-    map[start_row][start_column] = start_tile;
-
-    let inside = longest
-        .iter()
-        .find_map(|v| {
-            if map[v.0][v.1] == 'F' {
-                Some((v.0 * 2 + 1, v.1 * 2 + 1))
-            } else if map[v.0][v.1] == '7' {
-                Some((v.0 * 2 + 1, v.1 * 2 - 1))
-            } else if map[v.0][v.1] == 'J' {
-                Some((v.0 * 2 - 1, v.1 * 2 - 1))
-            } else if map[v.0][v.1] == 'L' {
-                Some((v.0 * 2 - 1, v.1 * 2 + 1))
-            } else {
-                None
-            }
-        })
-        .ok_or(AoCError::from("no corner"))?;
-    let mut visited = BTreeSet::<(usize, usize)>::new();
-    fill(&map, inside.0, inside.1, &mut visited);
-    let visited = visited
-        .iter()
-        .flat_map(|v| {
-            let row = v.0;
-            let column = v.1;
-            let tl = (row / 2, column / 2);
-            let tr = (row / 2, column / 2 + 1);
-            let bl = (row / 2 + 1, column / 2);
-            let br = (row / 2 + 1, column / 2 + 1);
-            [tl, tr, bl, br].into_iter()
-        })
-        .collect::<BTreeSet<(usize, usize)>>();
-    Ok((visited.len() - longest.len()).to_string())
 }
 
-fn run_map(
-    map: &Vec<Vec<char>>,
-    start_tile: char,
-    start_direction: Direction,
-    start_row: usize,
-    start_column: usize,
-) -> Option<(char, BTreeSet<(usize, usize)>)> {
-    let mut row = start_row;
-    let mut column = start_column;
-    let mut direction = start_direction;
-    let mut steps = BTreeSet::<(usize, usize)>::new();
-    loop {
-        steps.insert((row, column));
-        let tile = *map.get(row).and_then(|r| r.get(column))?;
-        let tile = (tile != 'S').then_some(tile).unwrap_or(start_tile);
-        match tile {
-            '|' => match direction {
-                North => {
-                    row = row.wrapping_sub(1);
-                }
-                South => {
-                    row = row.wrapping_add(1);
-                }
-                West => {
-                    break None;
-                }
-                East => {
-                    break None;
-                }
-            },
-            '-' => match direction {
-                North => {
-                    break None;
-                }
-                South => {
-                    break None;
-                }
-                West => {
-                    column = column.wrapping_sub(1);
-                }
-                East => {
-                    column = column.wrapping_add(1);
-                }
-            },
-            'L' => match direction {
-                North => {
-                    break None;
-                }
-                South => {
-                    column = column.wrapping_add(1);
-                    direction = East;
-                }
-                West => {
-                    row = row.wrapping_sub(1);
-                    direction = North;
-                }
-                East => {
-                    break None;
-                }
-            },
-            'J' => match direction {
-                North => {
-                    break None;
-                }
-                South => {
-                    column = column.wrapping_sub(1);
-                    direction = West;
-                }
-                West => {
-                    break None;
-                }
-                East => {
-                    row = row.wrapping_sub(1);
-                    direction = North;
-                }
-            },
-            '7' => match direction {
-                North => {
-                    column = column.wrapping_sub(1);
-                    direction = West;
-                }
-                South => {
-                    break None;
-                }
-                West => {
-                    break None;
-                }
-                East => {
-                    row = row.wrapping_add(1);
-                    direction = South;
-                }
-            },
-            'F' => match direction {
-                North => {
-                    column = column.wrapping_add(1);
-                    direction = East;
-                }
-                South => {
-                    break None;
-                }
-                West => {
-                    row = row.wrapping_add(1);
-                    direction = South;
-                }
-                East => {
-                    break None;
-                }
-            },
-            _ => {
-                break None;
-            }
-        }
-        if (row, column) == (start_row, start_column) {
-            break Some((start_tile, steps));
-        }
+fn opposite(direction: u8) -> u8 {
+    match direction {
+        NORTH => SOUTH,
+        SOUTH => NORTH,
+        WEST => EAST,
+        _ => WEST,
     }
 }
 
-fn fill(map: &Vec<Vec<char>>, row: usize, column: usize, visited: &mut BTreeSet<(usize, usize)>) {
-    if visited.contains(&(row, column)) {
-        return;
+impl Maze {
+    fn neighbour(&self, (row, column): (usize, usize), direction: u8) -> Option<(usize, usize)> {
+        let &(_, dr, dc) = DIRECTIONS.iter().find(|d| d.0 == direction)?;
+        let row = row.wrapping_add_signed(dr);
+        let column = column.wrapping_add_signed(dc);
+        self.tiles.get(row)?.get(column)?;
+        Some((row, column))
     }
-    visited.insert((row, column));
-    let tl = (row / 2, column / 2);
-    let tr = (row / 2, column / 2 + 1);
-    let bl = (row / 2 + 1, column / 2);
-    let br = (row / 2 + 1, column / 2 + 1);
-    let width = map[row / 2].len();
 
-    // north
-    let t1 = map[tl.0][tl.1];
-    let t2 = map[tr.0][tr.1];
-    if (t1 != 'L' && t1 != '-' && t1 != 'F') || (t2 != 'J' && t2 != '-' && t2 != '7') {
-        let nr = (row as i64) - 2;
-        if nr > 0 {
-            fill(map, nr as usize, column, visited);
-        }
+    fn find_loop(&mut self) -> Result<Vec<(usize, usize)>, AoCError> {
+        let connected = DIRECTIONS
+            .iter()
+            .filter(|&&(direction, _, _)| {
+                self.neighbour(self.start, direction)
+                    .is_some_and(|(r, c)| self.tiles[r][c] & opposite(direction) != 0)
+            })
+            .map(|d| d.0)
+            .collect::<Vec<u8>>();
+        let (start_mask, path) = connected
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &a)| connected[i + 1..].iter().map(move |&b| a | b))
+            .find_map(|mask| self.walk(mask).map(|path| (mask, path)))
+            .ok_or(AoCError::from("no loop"))?;
+        self.tiles[self.start.0][self.start.1] = start_mask;
+        Ok(path)
     }
 
-    // south
-    let t1 = map[bl.0][bl.1];
-    let t2 = map[br.0][br.1];
-    if (t1 != 'L' && t1 != '-' && t1 != 'F') || (t2 != 'J' && t2 != '-' && t2 != '7') {
-        let nr = row + 2;
-        if nr < (map.len() - 1) * 2 {
-            fill(map, nr, column, visited);
+    fn walk(&self, start_mask: u8) -> Option<Vec<(usize, usize)>> {
+        let mut position = self.start;
+        let mut heading = start_mask & start_mask.wrapping_neg();
+        let mut path = Vec::new();
+        loop {
+            path.push(position);
+            position = self.neighbour(position, heading)?;
+            let from = opposite(heading);
+            if position == self.start {
+                break (start_mask & from != 0).then_some(path);
+            }
+            let tile = self.tiles[position.0][position.1];
+            if tile & from == 0 {
+                break None;
+            }
+            heading = tile & !from;
         }
     }
+}
 
-    // west
-    let t1 = map[tl.0][tl.1];
-    let t2 = map[bl.0][bl.1];
-    if (t1 != '7' && t1 != '|' && t1 != 'F') || (t2 != 'J' && t2 != '|' && t2 != 'L') {
-        let nc = (column as i64) - 2;
-        if nc > 0 {
-            fill(map, row, nc as usize, visited);
-        }
-    }
+fn shoelace(path: &[(usize, usize)]) -> usize {
+    let doubled_area = path
+        .iter()
+        .zip(path.iter().cycle().skip(1))
+        .fold(0i64, |a, (p, q)| {
+            a + p.0 as i64 * q.1 as i64 - p.1 as i64 * q.0 as i64
+        })
+        .unsigned_abs() as usize;
+    (doubled_area + 2 - path.len()) / 2
+}
 
-    // east
-    let t1 = map[tr.0][tr.1];
-    let t2 = map[br.0][br.1];
-    if (t1 != '7' && t1 != '|' && t1 != 'F') || (t2 != 'J' && t2 != '|' && t2 != 'L') {
-        let nc = column + 2;
-        if nc < (width - 1) * 2 {
-            fill(map, row, nc, visited);
-        }
-    }
+fn scanline(tiles: &[Vec<u8>], path: &[(usize, usize)]) -> Vec<Vec<bool>> {
+    let path = path.iter().copied().collect::<BTreeSet<(usize, usize)>>();
+    tiles
+        .iter()
+        .enumerate()
+        .map(|(row, line)| {
+            let mut inside = false;
+            line.iter()
+                .enumerate()
+                .map(|(column, tile)| {
+                    if path.contains(&(row, column)) {
+                        if tile & NORTH != 0 {
+                            inside = !inside;
+                        }
+                        false
+                    } else {
+                        inside
+                    }
+                })
+                .collect()
+        })
+        .collect()
 }
@@ -1,18 +1,37 @@
 use crate::error::AoCError;
-use alloc::collections::BTreeSet;
+use crate::utils::parse_options;
+use alloc::format;
 use alloc::string::{String, ToString};
+use alloc::vec;
 use alloc::vec::Vec;
-use core::cmp::{max, min};
 
 pub(crate) fn part1(input: String) -> Result<String, AoCError> {
-    solve(input, 2)
+    Ok(solve(&input, &[2])?[0].to_string())
 }
 
 pub(crate) fn part2(input: String) -> Result<String, AoCError> {
-    solve(input, 1000000)
+    Ok(solve(&input, &[1000000])?[0].to_string())
 }
 
-fn solve(input: String, expansion: u64) -> Result<String, AoCError> {
+pub(crate) fn details(input: String) -> Result<String, AoCError> {
+    let (options, _) = parse_options(&input, &["expansion"])?;
+    let expansions = options
+        .get("expansion")
+        .copied()
+        .unwrap_or("2 1000000")
+        .split(' ')
+        .map(|v| v.parse::<u128>())
+        .collect::<Result<Vec<u128>, _>>()?;
+    Ok(solve(&input, &expansions)?
+        .into_iter()
+        .zip(expansions)
+        .map(|(s, expansion)| format!("Expansion {}: {}", expansion, s))
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+fn solve(input: &str, expansions: &[u128]) -> Result<Vec<u128>, AoCError> {
+    let (_, input) = parse_options(input, &["expansion"])?;
     let map = input
         .trim()
         .lines()
@@ -29,23 +48,45 @@ fn solve(input: String, expansion: u64) -> Result<String, AoCError> {
                 .map(move |c| (row.0, c.0))
         })
         .collect::<Vec<(usize, usize)>>();
-    let star_rows = stars.iter().map(|c| c.0).collect::<BTreeSet<usize>>();
-    let star_columns = stars.iter().map(|c| c.1).collect::<BTreeSet<usize>>();
-    let s = stars
+    let width = map.iter().map(|row| row.len()).max().unwrap_or(0);
+    let (rows, row_gaps) = distances(stars.iter().map(|c| c.0).collect(), map.len());
+    let (columns, column_gaps) = distances(stars.iter().map(|c| c.1).collect(), width);
+    expansions
         .iter()
-        .enumerate()
-        .flat_map(|(i, x)| {
-            stars[(i + 1)..].iter().map(|y| {
-                let rmm = min(x.0, y.0);
-                let rmx = max(x.0, y.0);
-                let cmm = min(x.1, y.1);
-                let cmx = max(x.1, y.1);
-                let er = (rmm..rmx).filter(|v| !star_rows.contains(v)).count();
-                let ec = (cmm..cmx).filter(|v| !star_columns.contains(v)).count();
-                let s = (rmx - rmm) + (cmx - cmm);
-                s as u64 + (er + ec) as u64 * (expansion - 1)
-            })
+        .map(|&expansion| {
+            if expansion == 0 {
+                return Err(AoCError::from("invalid expansion"));
+            }
+            (expansion - 1)
+                .checked_mul(row_gaps + column_gaps)
+                .and_then(|e| e.checked_add(rows + columns))
+                .ok_or(AoCError::from("overflow"))
+        })
+        .collect()
+}
+
+fn distances(mut coordinates: Vec<usize>, size: usize) -> (u128, u128) {
+    coordinates.sort_unstable();
+    let mut occupied = vec![false; size];
+    coordinates.iter().for_each(|&c| occupied[c] = true);
+    let gaps = occupied
+        .iter()
+        .scan(0u128, |gaps, &occupied| {
+            *gaps += !occupied as u128;
+            Some(*gaps)
         })
-        .sum::<u64>();
-    Ok(s.to_string())
+        .collect::<Vec<u128>>();
+    let n = coordinates.len() as i128;
+    let (distance, expansion) =
+        coordinates
+            .iter()
+            .enumerate()
+            .fold((0, 0), |(distance, expansion), (i, &c)| {
+                let weight = 2 * i as i128 - n + 1;
+                (
+                    distance + c as i128 * weight,
+                    expansion + gaps[c] as i128 * weight,
+                )
+            });
+    (distance as u128, expansion as u128)
 }
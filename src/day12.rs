@@ -1,76 +1,231 @@
 use crate::error::AoCError;
-use alloc::collections::BTreeMap;
+use crate::utils::parse_options;
+use alloc::format;
 use alloc::string::{String, ToString};
+use alloc::vec;
 use alloc::vec::Vec;
+use bnum::types::U512;
 use core::num::ParseIntError;
 
+const OPTIONS: &[&str] = &["unfold", "first", "rank", "random"];
+
+struct Record {
+    springs: Vec<u8>,
+    groups: Vec<usize>,
+    runs: Vec<usize>,
+    damaged: Vec<usize>,
+}
+
 pub(crate) fn part1(input: String) -> Result<String, AoCError> {
+    let (_, input) = parse_options(&input, OPTIONS)?;
     solve(input, 1)
 }
 
 pub(crate) fn part2(input: String) -> Result<String, AoCError> {
-    solve(input, 5)
+    let (options, input) = parse_options(&input, OPTIONS)?;
+    let multiplier = options
+        .get("unfold")
+        .map_or(Ok(5), |v| v.parse::<usize>())?;
+    solve(input, multiplier)
+}
+
+pub(crate) fn details(input: String) -> Result<String, AoCError> {
+    let (options, input) = parse_options(&input, OPTIONS)?;
+    let multiplier = options
+        .get("unfold")
+        .map_or(Ok(1), |v| v.parse::<usize>())?;
+    let first = options.get("first").map_or(Ok(1), |v| v.parse::<usize>())?;
+    let rank = options.get("rank").map(|v| v.parse::<U512>()).transpose()?;
+    let mut random = options
+        .get("random")
+        .map(|v| v.parse::<u64>())
+        .transpose()?
+        .map(SplitMix64);
+    let mut lines = Vec::new();
+    for record in parse_records(input, multiplier)? {
+        let table = record.count_table()?;
+        let count = table[0][0];
+        lines.push(format!(
+            "{} {:?}: {} arrangements",
+            String::from_utf8_lossy(&record.springs),
+            record.groups,
+            count
+        ));
+        let mut index = U512::ZERO;
+        while index < count && index < U512::from(first as u64) {
+            lines.push(format!("  {}", record.unrank(&table, index)));
+            index += U512::ONE;
+        }
+        if let Some(rank) = rank.filter(|&rank| rank < count) {
+            lines.push(format!("  #{}: {}", rank, record.unrank(&table, rank)));
+        }
+        if let Some(random) = random.as_mut().filter(|_| count > U512::ZERO) {
+            let rank = random.below(count);
+            lines.push(format!(
+                "  random #{}: {}",
+                rank,
+                record.unrank(&table, rank)
+            ));
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+fn solve(input: &str, multiplier: usize) -> Result<String, AoCError> {
+    let s = parse_records(input, multiplier)?
+        .iter()
+        .map(|record| Ok(record.count_table()?[0][0]))
+        .collect::<Result<Vec<U512>, AoCError>>()?
+        .into_iter()
+        .try_fold(U512::ZERO, |a, c| a.checked_add(c))
+        .ok_or(AoCError::from("overflow"))?;
+    Ok(s.to_string())
 }
 
-fn solve(input: String, multiplier: usize) -> Result<String, AoCError> {
-    let s = input
+fn parse_records(input: &str, multiplier: usize) -> Result<Vec<Record>, AoCError> {
+    if multiplier == 0 {
+        return Err(AoCError::from("invalid unfold multiplier"));
+    }
+    input
+        .trim()
         .lines()
         .map(|line| {
             let (template, numbers) = line
                 .split_once(' ')
                 .ok_or(AoCError::from("invalid format"))?;
-            let mut template = template.chars().collect::<Vec<char>>();
-            template.push('?');
-            template = template.repeat(multiplier);
-            let n = template.len() - 1;
-            template[n] = '.';
+            if template.bytes().any(|c| !matches!(c, b'.' | b'#' | b'?')) {
+                return Err(AoCError::from("unknown spring"));
+            }
             let numbers = numbers
                 .split(',')
                 .map(|x| x.parse::<usize>())
                 .collect::<Result<Vec<usize>, ParseIntError>>()?;
-            let numbers = numbers.repeat(multiplier);
-            let mut cache = BTreeMap::<(usize, usize, usize), u64>::new();
-            Ok(find_maps(&mut template, 0, &numbers, 0, 0, &mut cache))
+            if numbers.contains(&0) {
+                return Err(AoCError::from("empty group"));
+            }
+            let template = vec![template; multiplier].join("?");
+            Ok(Record::new(
+                template.into_bytes(),
+                numbers.repeat(multiplier),
+            ))
         })
-        .collect::<Result<Vec<u64>, AoCError>>()?;
-    Ok(s.iter().sum::<u64>().to_string())
+        .collect()
 }
 
-fn find_maps(
-    template: &mut Vec<char>,
-    idx: usize,
-    correct: &Vec<usize>,
-    block: usize,
-    cur: usize,
-    cache: &mut BTreeMap<(usize, usize, usize), u64>,
-) -> u64 {
-    let key = (idx, block, cur);
-    if let Some(res) = cache.get(&key) {
-        return *res;
+impl Record {
+    fn new(springs: Vec<u8>, groups: Vec<usize>) -> Self {
+        let mut runs = vec![0; springs.len() + 1];
+        for i in (0..springs.len()).rev() {
+            runs[i] = if springs[i] == b'.' {
+                0
+            } else {
+                runs[i + 1] + 1
+            };
+        }
+        let damaged = springs
+            .iter()
+            .scan(0, |count, &c| {
+                *count += (c == b'#') as usize;
+                Some(*count)
+            })
+            .collect::<Vec<usize>>();
+        Record {
+            springs,
+            groups,
+            runs,
+            damaged: [0].into_iter().chain(damaged).collect(),
+        }
     }
-    if idx == template.len() {
-        return (block == correct.len()) as u64;
+
+    fn operational(&self, from: usize, to: usize) -> bool {
+        self.damaged[to] == self.damaged[from]
     }
-    let res = ['.', '#']
-        .iter()
-        .map(|c| {
-            if template[idx] == '?' || template[idx] == *c {
-                if *c == '.' {
-                    if cur == 0 {
-                        find_maps(template, idx + 1, correct, block, 0, cache)
-                    } else if correct.get(block) == Some(&cur) {
-                        find_maps(template, idx + 1, correct, block + 1, 0, cache)
-                    } else {
-                        0
-                    }
-                } else {
-                    find_maps(template, idx + 1, correct, block, cur + 1, cache)
+
+    fn fits(&self, idx: usize, len: usize) -> bool {
+        self.runs[idx] >= len && self.springs.get(idx + len) != Some(&b'#')
+    }
+
+    fn count_table(&self) -> Result<Vec<Vec<U512>>, AoCError> {
+        let n = self.springs.len();
+        let m = self.groups.len();
+        let mut table = vec![vec![U512::ZERO; n + 2]; m + 1];
+        for (idx, ways) in table[m].iter_mut().enumerate().take(n + 1) {
+            if self.operational(idx, n) {
+                *ways = U512::ONE;
+            }
+        }
+        let mut needed = 0;
+        for block in (0..m).rev() {
+            let len = self.groups[block];
+            needed += len + (block + 1 < m) as usize;
+            for idx in (0..n).rev() {
+                if n - idx < needed {
+                    continue;
+                }
+                let mut ways = U512::ZERO;
+                if self.springs[idx] != b'#' {
+                    ways = table[block][idx + 1];
                 }
+                if self.fits(idx, len) {
+                    ways = ways
+                        .checked_add(table[block + 1][(idx + len + 1).min(n)])
+                        .ok_or(AoCError::from("overflow"))?;
+                }
+                table[block][idx] = ways;
+            }
+        }
+        Ok(table)
+    }
+
+    fn unrank(&self, table: &[Vec<U512>], mut rank: U512) -> String {
+        let n = self.springs.len();
+        let mut arrangement = String::new();
+        let mut idx = 0;
+        let mut block = 0;
+        while idx < n {
+            let placed = match self.groups.get(block) {
+                Some(&len) if self.fits(idx, len) => table[block + 1][(idx + len + 1).min(n)],
+                _ => U512::ZERO,
+            };
+            if rank < placed {
+                let len = self.groups[block];
+                (0..len).for_each(|_| arrangement.push('#'));
+                if idx + len < n {
+                    arrangement.push('.');
+                }
+                idx += len + 1;
+                block += 1;
             } else {
-                0
+                rank -= placed;
+                arrangement.push('.');
+                idx += 1;
             }
-        })
-        .sum::<u64>();
-    cache.insert(key, res);
-    res
+        }
+        arrangement
+    }
+}
+
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: U512) -> U512 {
+        let bits = bound.bits();
+        loop {
+            let value = (0..bits.div_ceil(64)).fold(U512::ZERO, |value, _| {
+                (value << 64u32) | U512::from(self.next())
+            });
+            let value = value & (U512::MAX >> (U512::BITS - bits));
+            if value < bound {
+                break value;
+            }
+        }
+    }
 }
@@ -1,7 +1,24 @@
 use crate::error::AoCError;
+use crate::utils::parse_options;
+use alloc::format;
 use alloc::string::{String, ToString};
+use alloc::vec;
 use alloc::vec::Vec;
 
+const OPTIONS: &[&str] = &["max_smudges"];
+
+struct Pattern {
+    rows: Vec<u64>,
+    columns: Vec<u64>,
+}
+
+struct Reflection {
+    vertical: bool,
+    axis: usize,
+    smudges: u32,
+    smudge: Option<(usize, usize)>,
+}
+
 pub(crate) fn part1(input: String) -> Result<String, AoCError> {
     solve(input, 0)
 }
@@ -10,43 +27,114 @@ pub(crate) fn part2(input: String) -> Result<String, AoCError> {
     solve(input, 1)
 }
 
-fn solve(input: String, smudges: usize) -> Result<String, AoCError> {
-    Ok(input
-        .trim()
-        .split("\n\n")
-        .map(|pattern| {
-            let rows = pattern.split('\n').collect::<Vec<_>>();
-            (1..rows[0].len())
-                .find(|i| {
-                    rows.iter()
-                        .map(|row| {
-                            row[..*i]
-                                .chars()
-                                .rev()
-                                .zip(row[*i..].chars())
-                                .filter(|(x, y)| x != y)
-                                .count()
-                        })
-                        .sum::<usize>()
-                        == smudges
-                })
-                .or_else(|| {
-                    (1..rows.len())
-                        .find(|i| {
-                            rows[..*i]
-                                .iter()
-                                .rev()
-                                .zip(rows[*i..].iter())
-                                .map(|(x, y)| {
-                                    x.chars().zip(y.chars()).filter(|(u, v)| u != v).count()
-                                })
-                                .sum::<usize>()
-                                == smudges
-                        })
-                        .map(|v| v * 100)
+pub(crate) fn details(input: String) -> Result<String, AoCError> {
+    let (options, _) = parse_options(&input, OPTIONS)?;
+    let max_smudges = options
+        .get("max_smudges")
+        .map_or(Ok(1), |v| v.parse::<u32>())?;
+    Ok(parse_patterns(&input)?
+        .iter()
+        .enumerate()
+        .map(|(i, pattern)| {
+            let axes = pattern
+                .reflections()
+                .into_iter()
+                .filter(|r| r.smudges <= max_smudges)
+                .map(|r| {
+                    let kind = if r.vertical { "vertical" } else { "horizontal" };
+                    match r.smudge {
+                        Some((row, column)) => format!(
+                            "{} {} (1 smudge at row {}, column {})",
+                            kind,
+                            r.axis,
+                            row + 1,
+                            column + 1
+                        ),
+                        None => format!("{} {} ({} smudges)", kind, r.axis, r.smudges),
+                    }
                 })
-                .unwrap_or(0)
+                .collect::<Vec<String>>();
+            format!("Pattern {}: {}", i + 1, axes.join(", "))
+        })
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+fn solve(input: String, smudges: u32) -> Result<String, AoCError> {
+    Ok(parse_patterns(&input)?
+        .iter()
+        .map(|pattern| {
+            pattern
+                .reflections()
+                .into_iter()
+                .find(|r| r.smudges == smudges)
+                .map_or(0, |r| if r.vertical { r.axis } else { r.axis * 100 })
         })
         .sum::<usize>()
         .to_string())
 }
+
+fn parse_patterns(input: &str) -> Result<Vec<Pattern>, AoCError> {
+    let (_, input) = parse_options(input, OPTIONS)?;
+    input
+        .trim()
+        .split("\n\n")
+        .map(|pattern| {
+            let lines = pattern.lines().map(|l| l.as_bytes()).collect::<Vec<_>>();
+            let width = lines.first().map_or(0, |l| l.len());
+            if lines.len() > 64 || width > 64 {
+                return Err(AoCError::from("pattern too large"));
+            }
+            if lines.iter().any(|l| l.len() != width) {
+                return Err(AoCError::from("ragged pattern"));
+            }
+            let mut rows = vec![0u64; lines.len()];
+            let mut columns = vec![0u64; width];
+            for (r, line) in lines.iter().enumerate() {
+                for (c, &tile) in line.iter().enumerate() {
+                    if tile == b'#' {
+                        rows[r] |= 1 << c;
+                        columns[c] |= 1 << r;
+                    }
+                }
+            }
+            Ok(Pattern { rows, columns })
+        })
+        .collect()
+}
+
+impl Pattern {
+    fn reflections(&self) -> Vec<Reflection> {
+        axes(&self.columns)
+            .map(|(axis, smudges, smudge)| Reflection {
+                vertical: true,
+                axis,
+                smudges,
+                smudge: smudge.map(|(column, row)| (row, column)),
+            })
+            .chain(axes(&self.rows).map(|(axis, smudges, smudge)| Reflection {
+                vertical: false,
+                axis,
+                smudges,
+                smudge,
+            }))
+            .collect()
+    }
+}
+
+fn axes(lines: &[u64]) -> impl Iterator<Item = (usize, u32, Option<(usize, usize)>)> + '_ {
+    (1..lines.len()).map(|axis| {
+        let mirrored = lines[..axis]
+            .iter()
+            .rev()
+            .zip(&lines[axis..])
+            .enumerate()
+            .map(|(k, (a, b))| (axis - 1 - k, a ^ b));
+        let smudges = mirrored.clone().map(|(_, d)| d.count_ones()).sum::<u32>();
+        let smudge = (smudges == 1)
+            .then(|| mirrored.clone().find(|(_, d)| *d != 0))
+            .flatten()
+            .map(|(line, d)| (line, d.trailing_zeros() as usize));
+        (axis, smudges, smudge)
+    })
+}
@@ -1,85 +1,128 @@
 use crate::error::AoCError;
-use alloc::collections::{BTreeMap, BTreeSet};
+use crate::utils::parse_options;
+use alloc::collections::BTreeMap;
 use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+const OPTIONS: &[&str] = &["sequence", "cycles"];
+
+#[derive(Clone, Copy)]
+enum Direction {
+    North,
+    West,
+    South,
+    East,
+}
+
+struct Platform {
+    tiles: Vec<u8>,
+    width: usize,
+    height: usize,
+}
 
 pub(crate) fn part1(input: String) -> Result<String, AoCError> {
-    let input = input.trim();
-    let height = input.lines().count();
-    Ok(score(tilt(parse_stones(input)), height).to_string())
+    let (_, input) = parse_options(&input, OPTIONS)?;
+    let mut platform = parse_platform(input)?;
+    platform.tilt(Direction::North);
+    Ok(platform.load().to_string())
 }
 
 pub(crate) fn part2(input: String) -> Result<String, AoCError> {
-    let input = input.trim();
-    let height = input.lines().count();
-    let mut stones = parse_stones(input);
-    let target = 1000000000;
-    let mut idx = 0;
-    let mut cache = BTreeMap::<BTreeSet<(usize, usize, bool)>, usize>::new();
-    stones = loop {
-        for _ in 0..4 {
-            stones = tilt(stones);
-            stones = rotate(stones, height);
-        }
-        idx += 1;
-        if let Some(t) = cache.get(&stones) {
-            let n = idx - t;
-            let x = (target - t) % n + t;
-            let res = cache
-                .iter()
-                .find_map(|(key, value)| (*value == x).then_some(key))
-                .ok_or(AoCError::from("not found in cache"))?;
-            break res.clone();
-        }
-        cache.insert(stones.clone(), idx);
-    };
-    Ok(score(stones, height).to_string())
+    let (options, input) = parse_options(&input, OPTIONS)?;
+    let sequence = parse_sequence(options.get("sequence").copied().unwrap_or("NWSE"))?;
+    let cycles = options
+        .get("cycles")
+        .map_or(Ok(1000000000), |v| v.parse::<u64>())?;
+    let mut platform = parse_platform(input)?;
+    platform.spin(&sequence, cycles);
+    Ok(platform.load().to_string())
 }
 
-fn parse_stones(input: &str) -> BTreeSet<(usize, usize, bool)> {
-    input
-        .trim()
-        .lines()
-        .enumerate()
-        .flat_map(|(ri, row)| {
-            row.chars().enumerate().filter_map(move |(ci, c)| match c {
-                '#' => Some((ci, ri, false)),
-                'O' => Some((ci, ri, true)),
-                _ => None,
-            })
-        })
-        .collect()
+fn parse_platform(input: &str) -> Result<Platform, AoCError> {
+    let lines = input.trim().lines().collect::<Vec<&str>>();
+    let width = lines.first().map_or(0, |l| l.len());
+    if lines.iter().any(|l| l.len() != width) {
+        return Err(AoCError::from("ragged platform"));
+    }
+    if lines
+        .iter()
+        .flat_map(|l| l.bytes())
+        .any(|c| !matches!(c, b'.' | b'#' | b'O'))
+    {
+        return Err(AoCError::from("unknown tile"));
+    }
+    Ok(Platform {
+        tiles: lines.iter().flat_map(|l| l.bytes()).collect(),
+        width,
+        height: lines.len(),
+    })
 }
 
-fn tilt(stones: BTreeSet<(usize, usize, bool)>) -> BTreeSet<(usize, usize, bool)> {
-    let mut limit = (0usize, 0usize);
-    stones
-        .into_iter()
-        .map(|(ci, ri, moving)| {
-            if ci != limit.0 {
-                limit = (ci, 0);
-            }
-            if moving {
-                let new = (ci, limit.1, true);
-                limit = (ci, limit.1 + 1);
-                new
-            } else {
-                limit = (ci, ri + 1);
-                (ci, ri, false)
-            }
+fn parse_sequence(sequence: &str) -> Result<Vec<Direction>, AoCError> {
+    if sequence.is_empty() {
+        return Err(AoCError::from("empty sequence"));
+    }
+    sequence
+        .chars()
+        .map(|c| match c {
+            'N' => Ok(Direction::North),
+            'W' => Ok(Direction::West),
+            'S' => Ok(Direction::South),
+            'E' => Ok(Direction::East),
+            _ => Err(AoCError::from("unknown direction")),
         })
         .collect()
 }
 
-fn score(stones: BTreeSet<(usize, usize, bool)>, height: usize) -> u64 {
-    stones
-        .into_iter()
-        .filter_map(|(_, ri, moving)| moving.then_some((height - ri) as u64))
-        .sum()
-}
+impl Platform {
+    fn tilt(&mut self, direction: Direction) {
+        let (width, height) = (self.width, self.height);
+        let (lines, length) = match direction {
+            Direction::North | Direction::South => (width, height),
+            Direction::West | Direction::East => (height, width),
+        };
+        let index = |line: usize, k: usize| match direction {
+            Direction::North => k * width + line,
+            Direction::South => (height - 1 - k) * width + line,
+            Direction::West => line * width + k,
+            Direction::East => line * width + width - 1 - k,
+        };
+        for line in 0..lines {
+            let mut free = 0;
+            for k in 0..length {
+                match self.tiles[index(line, k)] {
+                    b'#' => free = k + 1,
+                    b'O' => {
+                        self.tiles[index(line, k)] = b'.';
+                        self.tiles[index(line, free)] = b'O';
+                        free += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
 
-fn rotate(stones: BTreeSet<(usize, usize, bool)>, height: usize) -> BTreeSet<(usize, usize, bool)> {
-    stones
-        .into_iter()
-        .map(|(ci, ri, moving)| (height - ri - 1, ci, moving))
-        .collect()
+    fn spin(&mut self, sequence: &[Direction], cycles: u64) {
+        let mut seen = BTreeMap::<Vec<u8>, u64>::new();
+        let mut history = Vec::new();
+        for cycle in 0..cycles {
+            if let Some(&start) = seen.get(&self.tiles) {
+                let offset = (cycles - start) % (cycle - start);
+                self.tiles = history.swap_remove((start + offset) as usize);
+                return;
+            }
+            seen.insert(self.tiles.clone(), cycle);
+            history.push(self.tiles.clone());
+            sequence.iter().for_each(|&direction| self.tilt(direction));
+        }
+    }
+
+    fn load(&self) -> usize {
+        self.tiles
+            .chunks(self.width.max(1))
+            .enumerate()
+            .map(|(row, line)| (self.height - row) * line.iter().filter(|&&c| c == b'O').count())
+            .sum()
+    }
 }
@@ -1,9 +1,37 @@
 use crate::error::AoCError;
+use crate::utils::parse_options;
+use alloc::format;
 use alloc::string::{String, ToString};
+use alloc::vec;
 use alloc::vec::Vec;
-use core::num::ParseIntError;
+
+const OPTIONS: &[&str] = &["trace"];
+const BOXES: usize = 256;
+const NONE: usize = usize::MAX;
+
+enum Step<'a> {
+    Insert(&'a str, u64),
+    Remove(&'a str),
+}
+
+struct Lens<'a> {
+    label: &'a str,
+    focal_length: u64,
+    bucket: usize,
+    prev: usize,
+    next: usize,
+    active: bool,
+}
+
+struct LensBoxes<'a> {
+    lenses: Vec<Lens<'a>>,
+    index: Vec<usize>,
+    heads: [usize; BOXES],
+    tails: [usize; BOXES],
+}
 
 pub(crate) fn part1(input: String) -> Result<String, AoCError> {
+    let (_, input) = parse_options(&input, OPTIONS)?;
     Ok(input
         .trim()
         .split(',')
@@ -13,44 +41,226 @@ pub(crate) fn part1(input: String) -> Result<String, AoCError> {
 }
 
 pub(crate) fn part2(input: String) -> Result<String, AoCError> {
-    let mut buckets = Vec::<Vec<(&str, u64)>>::new();
-    for _ in 0..256 {
-        buckets.push(Vec::new())
+    let (_, input) = parse_options(&input, OPTIONS)?;
+    let mut boxes = LensBoxes::new();
+    for (_, step) in parse_steps(input)? {
+        boxes.apply(step);
     }
+    Ok(boxes.power().to_string())
+}
+
+pub(crate) fn details(input: String) -> Result<String, AoCError> {
+    let (options, input) = parse_options(&input, OPTIONS)?;
+    let trace = options.get("trace").map_or(Ok(0), |v| v.parse::<usize>())?;
+    let mut boxes = LensBoxes::new();
+    let mut lines = Vec::new();
+    for (i, (text, step)) in parse_steps(input)?.into_iter().enumerate() {
+        boxes.apply(step);
+        if i < trace {
+            lines.push(format!("After \"{}\":", text));
+            lines.extend(render(&boxes));
+            lines.push(String::new());
+        }
+    }
+    lines.push(String::from("Final state:"));
+    lines.extend(render(&boxes));
+    lines.push(format!("Focusing power: {}", boxes.power()));
+    lines.push(String::new());
+    lines.push(String::from("Final state (JSON):"));
+    lines.push(json(&boxes));
+    Ok(lines.join("\n"))
+}
+
+fn json(boxes: &LensBoxes<'_>) -> String {
+    let boxes = boxes
+        .state()
+        .into_iter()
+        .map(|(bucket, lenses)| {
+            let lenses = lenses
+                .iter()
+                .map(|(label, focal_length)| {
+                    format!(
+                        "{{\"label\":{},\"focal_length\":{}}}",
+                        json_string(label),
+                        focal_length
+                    )
+                })
+                .collect::<Vec<String>>();
+            format!("{{\"box\":{},\"lenses\":[{}]}}", bucket, lenses.join(","))
+        })
+        .collect::<Vec<String>>();
+    format!("[{}]", boxes.join(","))
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn render(boxes: &LensBoxes<'_>) -> Vec<String> {
+    boxes
+        .state()
+        .into_iter()
+        .map(|(bucket, lenses)| {
+            let lenses = lenses
+                .iter()
+                .map(|(label, focal_length)| format!("[{} {}]", label, focal_length))
+                .collect::<Vec<String>>();
+            format!("Box {}: {}", bucket, lenses.join(" "))
+        })
+        .collect()
+}
+
+fn parse_steps(input: &str) -> Result<Vec<(&str, Step<'_>)>, AoCError> {
     input
         .trim()
         .split(',')
-        .map(|command| {
-            if let Some((label, length)) = command.split_once('=') {
-                let key = hash(label);
-                let focal_length = length.parse::<u64>()?;
-                if let Some(pos) = buckets[key].iter().position(|e| e.0 == label) {
-                    buckets[key][pos].1 = focal_length;
-                } else {
-                    buckets[key].push((label, focal_length));
-                }
+        .map(|text| {
+            let step = if let Some((label, length)) = text.split_once('=') {
+                Step::Insert(label, length.parse::<u64>()?)
+            } else if let Some(label) = text.strip_suffix('-') {
+                Step::Remove(label)
             } else {
-                let label = command.trim_end_matches('-');
-                let key = hash(label);
-                if let Some(pos) = buckets[key].iter().position(|e| e.0 == label) {
-                    buckets[key].remove(pos);
-                }
-            }
-            Ok(())
+                return Err(AoCError::from("invalid step"));
+            };
+            Ok((text, step))
         })
-        .collect::<Result<Vec<()>, ParseIntError>>()?;
-    let s = buckets
-        .into_iter()
-        .enumerate()
-        .flat_map(|(bucket_idx, bucket)| {
-            bucket.into_iter().enumerate().map(move |(slot_idx, lens)| {
-                (1 + bucket_idx as u64) * (1 + slot_idx as u64) * lens.1
-            })
-        })
-        .sum::<u64>();
-    Ok(s.to_string())
+        .collect()
 }
 
 fn hash(s: &str) -> usize {
     s.chars().fold(0, |h, c| (h + (c as usize)) * 17 % 256)
 }
+
+fn fnv(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |h, c| {
+        (h ^ c as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl<'a> LensBoxes<'a> {
+    fn new() -> Self {
+        LensBoxes {
+            lenses: Vec::new(),
+            index: vec![NONE; 16],
+            heads: [NONE; BOXES],
+            tails: [NONE; BOXES],
+        }
+    }
+
+    fn apply(&mut self, step: Step<'a>) {
+        match step {
+            Step::Insert(label, focal_length) => self.insert(label, focal_length),
+            Step::Remove(label) => self.remove(label),
+        }
+    }
+
+    fn slot(&self, label: &str) -> usize {
+        let mask = self.index.len() - 1;
+        let mut slot = fnv(label) as usize & mask;
+        while self.index[slot] != NONE && self.lenses[self.index[slot]].label != label {
+            slot = (slot + 1) & mask;
+        }
+        slot
+    }
+
+    fn intern(&mut self, label: &'a str) -> usize {
+        if (self.lenses.len() + 1) * 2 > self.index.len() {
+            self.index = vec![NONE; self.index.len() * 2];
+            for id in 0..self.lenses.len() {
+                let slot = self.slot(self.lenses[id].label);
+                self.index[slot] = id;
+            }
+        }
+        let slot = self.slot(label);
+        if self.index[slot] == NONE {
+            self.index[slot] = self.lenses.len();
+            self.lenses.push(Lens {
+                label,
+                focal_length: 0,
+                bucket: hash(label),
+                prev: NONE,
+                next: NONE,
+                active: false,
+            });
+        }
+        self.index[slot]
+    }
+
+    fn insert(&mut self, label: &'a str, focal_length: u64) {
+        let id = self.intern(label);
+        self.lenses[id].focal_length = focal_length;
+        if self.lenses[id].active {
+            return;
+        }
+        let bucket = self.lenses[id].bucket;
+        let tail = self.tails[bucket];
+        self.lenses[id].active = true;
+        self.lenses[id].prev = tail;
+        self.lenses[id].next = NONE;
+        match tail {
+            NONE => self.heads[bucket] = id,
+            _ => self.lenses[tail].next = id,
+        }
+        self.tails[bucket] = id;
+    }
+
+    fn remove(&mut self, label: &str) {
+        let id = self.index[self.slot(label)];
+        if id == NONE || !self.lenses[id].active {
+            return;
+        }
+        let Lens {
+            bucket, prev, next, ..
+        } = self.lenses[id];
+        self.lenses[id].active = false;
+        match prev {
+            NONE => self.heads[bucket] = next,
+            _ => self.lenses[prev].next = next,
+        }
+        match next {
+            NONE => self.tails[bucket] = prev,
+            _ => self.lenses[next].prev = prev,
+        }
+    }
+
+    fn state(&self) -> Vec<(usize, Vec<(&'a str, u64)>)> {
+        (0..BOXES)
+            .filter(|&bucket| self.heads[bucket] != NONE)
+            .map(|bucket| {
+                let mut lenses = Vec::new();
+                let mut id = self.heads[bucket];
+                while id != NONE {
+                    lenses.push((self.lenses[id].label, self.lenses[id].focal_length));
+                    id = self.lenses[id].next;
+                }
+                (bucket, lenses)
+            })
+            .collect()
+    }
+
+    fn power(&self) -> u64 {
+        self.state()
+            .into_iter()
+            .flat_map(|(bucket, lenses)| {
+                lenses
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(slot, (_, focal_length))| {
+                        (1 + bucket as u64) * (1 + slot as u64) * focal_length
+                    })
+            })
+            .sum()
+    }
+}
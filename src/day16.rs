@@ -1,9 +1,14 @@
 use crate::error::AoCError;
-use alloc::collections::{BTreeSet, VecDeque};
+use crate::utils::parse_options;
+use alloc::collections::VecDeque;
+use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::vec;
 use alloc::vec::Vec;
 
+const OPTIONS: &[&str] = &["entry", "trace"];
+const NONE: usize = usize::MAX;
+
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
 enum Direction {
     Left,
@@ -12,6 +17,13 @@ enum Direction {
     Down,
 }
 
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Left,
+    Direction::Right,
+    Direction::Up,
+    Direction::Down,
+];
+
 impl Direction {
     fn delta(&self) -> (isize, isize) {
         match self {
@@ -21,137 +33,360 @@ impl Direction {
             Direction::Down => (1, 0),
         }
     }
+
+    fn deflect(&self, tile: u8) -> &'static [Direction] {
+        match (tile, self) {
+            (b'\\', Direction::Left) | (b'/', Direction::Right) => &[Direction::Up],
+            (b'\\', Direction::Right) | (b'/', Direction::Left) => &[Direction::Down],
+            (b'\\', Direction::Up) | (b'/', Direction::Down) => &[Direction::Left],
+            (b'\\', Direction::Down) | (b'/', Direction::Up) => &[Direction::Right],
+            (b'-', Direction::Up | Direction::Down) => &[Direction::Left, Direction::Right],
+            (b'|', Direction::Left | Direction::Right) => &[Direction::Up, Direction::Down],
+            (_, Direction::Left) => &[Direction::Left],
+            (_, Direction::Right) => &[Direction::Right],
+            (_, Direction::Up) => &[Direction::Up],
+            (_, Direction::Down) => &[Direction::Down],
+        }
+    }
 }
 
 type Beam = (usize, usize, Direction);
 
+struct Contraption {
+    tiles: Vec<u8>,
+    width: usize,
+    height: usize,
+}
+
 pub(crate) fn part1(input: String) -> Result<String, AoCError> {
-    let map = input
-        .trim()
-        .lines()
-        .map(|line| line.chars().collect::<Vec<_>>())
-        .collect::<Vec<_>>();
-    solve(&map, vec![(0, 0, Direction::Right)])
+    let contraption = parse_contraption(&input)?;
+    let trace = contraption.bfs((0, 0, Direction::Right));
+    Ok(contraption.energized(&trace).to_string())
 }
 
 pub(crate) fn part2(input: String) -> Result<String, AoCError> {
-    let map = input
-        .trim()
-        .lines()
-        .map(|line| line.chars().collect::<Vec<_>>())
-        .collect::<Vec<_>>();
-    let starts = (0..map.len())
-        .flat_map(|row| {
-            [
-                (row, 0, Direction::Right),
-                (row, map[row].len() - 1, Direction::Left),
-            ]
-            .into_iter()
-        })
-        .chain((0..map[0].len()).flat_map(|column| {
-            [
-                (0, column, Direction::Down),
-                (map.len() - 1, column, Direction::Up),
-            ]
-            .into_iter()
-        }))
-        .collect::<Vec<Beam>>();
-    solve(&map, starts)
+    let contraption = parse_contraption(&input)?;
+    let (_, energized) = contraption.best_entry()?;
+    Ok(energized.to_string())
+}
+
+pub(crate) fn details(input: String) -> Result<String, AoCError> {
+    let (options, _) = parse_options(&input, OPTIONS)?;
+    let contraption = parse_contraption(&input)?;
+    let (best, energized) = contraption.best_entry()?;
+    let mut lines = vec![format!(
+        "Best entry: {}, {} tiles energized",
+        describe(best),
+        energized
+    )];
+    let entry = match options.get("entry") {
+        Some(entry) => contraption.parse_entry(entry)?,
+        None => best,
+    };
+    let trace = contraption.bfs(entry);
+    let (beams, energized) = contraption.render(&trace);
+    lines.push(format!(
+        "Entry: {}, {} tiles energized",
+        describe(entry),
+        contraption.energized(&trace)
+    ));
+    lines.push(String::new());
+    lines.push(beams);
+    lines.push(String::new());
+    lines.push(energized);
+    let steps = options.get("trace").map_or(Ok(0), |v| v.parse::<usize>())?;
+    if steps > 0 {
+        lines.push(String::new());
+        lines.extend(trace.into_iter().take(steps).map(describe));
+    }
+    Ok(lines.join("\n"))
 }
 
-fn solve(map: &Vec<Vec<char>>, starts: Vec<Beam>) -> Result<String, AoCError> {
-    Ok(starts
-        .into_iter()
-        .map(|start| {
-            Ok(bfs(&map, start)?
-                .into_iter()
-                .map(|(row, column, _)| (row, column))
-                .collect::<BTreeSet<(usize, usize)>>()
-                .len())
-        })
-        .collect::<Result<Vec<usize>, AoCError>>()?
-        .into_iter()
-        .max()
-        .ok_or(AoCError::from("no path found"))?
-        .to_string())
+fn describe((row, column, direction): Beam) -> String {
+    let heading = match direction {
+        Direction::Left => "left",
+        Direction::Right => "right",
+        Direction::Up => "up",
+        Direction::Down => "down",
+    };
+    format!(
+        "row {}, column {}, heading {}",
+        row + 1,
+        column + 1,
+        heading
+    )
+}
+
+fn parse_contraption(input: &str) -> Result<Contraption, AoCError> {
+    let (_, input) = parse_options(input, OPTIONS)?;
+    let lines = input.trim().lines().collect::<Vec<&str>>();
+    let width = lines.first().map_or(0, |l| l.len());
+    if width == 0 {
+        return Err(AoCError::from("empty contraption"));
+    }
+    if lines.iter().any(|l| l.len() != width) {
+        return Err(AoCError::from("ragged contraption"));
+    }
+    let tiles = lines.iter().flat_map(|l| l.bytes()).collect::<Vec<u8>>();
+    if tiles
+        .iter()
+        .any(|c| !matches!(c, b'.' | b'\\' | b'/' | b'-' | b'|'))
+    {
+        return Err(AoCError::from("unknown tile"));
+    }
+    Ok(Contraption {
+        tiles,
+        width,
+        height: lines.len(),
+    })
 }
 
-fn bfs(map: &Vec<Vec<char>>, start: Beam) -> Result<BTreeSet<Beam>, AoCError> {
-    let mut queue = VecDeque::<Beam>::from([start]);
-    let mut visited = BTreeSet::<Beam>::new();
-    while !queue.is_empty() {
-        let current = queue.pop_front().unwrap();
-        if visited.contains(&current) {
-            continue;
+impl Contraption {
+    fn parse_entry(&self, entry: &str) -> Result<Beam, AoCError> {
+        let mut parts = entry.split(' ');
+        let (Some(row), Some(column), Some(direction), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(AoCError::from("entry should be row, column and heading"));
+        };
+        let row = row.parse::<usize>()?;
+        let column = column.parse::<usize>()?;
+        let direction = match direction {
+            "left" => Direction::Left,
+            "right" => Direction::Right,
+            "up" => Direction::Up,
+            "down" => Direction::Down,
+            _ => return Err(AoCError::from("unknown heading")),
+        };
+        if row == 0 || row > self.height || column == 0 || column > self.width {
+            return Err(AoCError::from("entry outside contraption"));
         }
-        if let Some(c) = map.get(current.0).and_then(|row| row.get(current.1)) {
-            visited.insert(current);
-            match c {
-                '.' => {
-                    let delta = current.2.delta();
-                    let new_row = current.0.wrapping_add_signed(delta.0);
-                    let new_column = current.1.wrapping_add_signed(delta.1);
-                    queue.push_back((new_row, new_column, current.2));
-                }
-                '\\' => {
-                    let new_direction = match current.2 {
-                        Direction::Left => Direction::Up,
-                        Direction::Right => Direction::Down,
-                        Direction::Up => Direction::Left,
-                        Direction::Down => Direction::Right,
-                    };
-                    let delta = new_direction.delta();
-                    let new_row = current.0.wrapping_add_signed(delta.0);
-                    let new_column = current.1.wrapping_add_signed(delta.1);
-                    queue.push_back((new_row, new_column, new_direction));
-                }
-                '/' => {
-                    let new_direction = match current.2 {
-                        Direction::Left => Direction::Down,
-                        Direction::Right => Direction::Up,
-                        Direction::Up => Direction::Right,
-                        Direction::Down => Direction::Left,
-                    };
-                    let delta = new_direction.delta();
-                    let new_row = current.0.wrapping_add_signed(delta.0);
-                    let new_column = current.1.wrapping_add_signed(delta.1);
-                    queue.push_back((new_row, new_column, new_direction));
-                }
-                '-' => match current.2 {
-                    Direction::Left | Direction::Right => {
-                        let delta = current.2.delta();
-                        let new_row = current.0.wrapping_add_signed(delta.0);
-                        let new_column = current.1.wrapping_add_signed(delta.1);
-                        queue.push_back((new_row, new_column, current.2));
-                    }
-                    Direction::Up | Direction::Down => {
-                        queue.push_back((
-                            current.0,
-                            current.1.wrapping_add_signed(-1),
-                            Direction::Left,
-                        ));
-                        queue.push_back((current.0, current.1.wrapping_add(1), Direction::Right));
+        Ok((row - 1, column - 1, direction))
+    }
+
+    fn state(&self, (row, column, direction): Beam) -> usize {
+        (row * self.width + column) * 4 + direction as usize
+    }
+
+    fn beam(&self, state: usize) -> Beam {
+        let tile = state / 4;
+        (tile / self.width, tile % self.width, DIRECTIONS[state % 4])
+    }
+
+    fn successors(&self, (row, column, direction): Beam) -> impl Iterator<Item = Beam> + '_ {
+        direction
+            .deflect(self.tiles[row * self.width + column])
+            .iter()
+            .filter_map(move |&direction| {
+                let (dr, dc) = direction.delta();
+                let row = row.wrapping_add_signed(dr);
+                let column = column.wrapping_add_signed(dc);
+                (row < self.height && column < self.width).then_some((row, column, direction))
+            })
+    }
+
+    fn entries(&self) -> Vec<Beam> {
+        (0..self.height)
+            .flat_map(|row| {
+                [
+                    (row, 0, Direction::Right),
+                    (row, self.width - 1, Direction::Left),
+                ]
+            })
+            .chain((0..self.width).flat_map(|column| {
+                [
+                    (0, column, Direction::Down),
+                    (self.height - 1, column, Direction::Up),
+                ]
+            }))
+            .collect()
+    }
+
+    fn headings(&self, trace: &[Beam]) -> Vec<u8> {
+        let mut headings = vec![0u8; self.tiles.len()];
+        for &(row, column, direction) in trace {
+            headings[row * self.width + column] |= 1 << direction as u8;
+        }
+        headings
+    }
+
+    fn energized(&self, trace: &[Beam]) -> usize {
+        self.headings(trace).iter().filter(|&&h| h != 0).count()
+    }
+
+    fn bfs(&self, start: Beam) -> Vec<Beam> {
+        let mut visited = vec![false; self.tiles.len() * 4];
+        let mut trace = Vec::new();
+        let mut queue = VecDeque::from([start]);
+        while let Some(current) = queue.pop_front() {
+            let state = self.state(current);
+            if visited[state] {
+                continue;
+            }
+            visited[state] = true;
+            trace.push(current);
+            queue.extend(self.successors(current));
+        }
+        trace
+    }
+
+    fn render(&self, trace: &[Beam]) -> (String, String) {
+        let headings = self.headings(trace);
+        let beams = self
+            .tiles
+            .iter()
+            .zip(&headings)
+            .map(|(&tile, &heading)| match (tile, heading.count_ones()) {
+                (b'.', 0) => '.',
+                (b'.', 1) => DIRECTIONS
+                    .iter()
+                    .zip(['<', '>', '^', 'v'])
+                    .find(|(&direction, _)| heading == 1 << direction as u8)
+                    .map_or('?', |(_, arrow)| arrow),
+                (b'.', n) => char::from_digit(n, 10).unwrap_or('?'),
+                (tile, _) => tile as char,
+            })
+            .collect::<Vec<char>>();
+        let energized = headings
+            .iter()
+            .map(|&heading| if heading != 0 { '#' } else { '.' })
+            .collect::<Vec<char>>();
+        let lines = |grid: Vec<char>| {
+            grid.chunks(self.width)
+                .map(|line| line.iter().collect::<String>())
+                .collect::<Vec<String>>()
+                .join("\n")
+        };
+        (lines(beams), lines(energized))
+    }
+
+    fn components(&self, edges: &[[usize; 2]]) -> (Vec<usize>, usize) {
+        let n = edges.len();
+        let mut index = vec![NONE; n];
+        let mut low = vec![0; n];
+        let mut on_stack = vec![false; n];
+        let mut stack = Vec::new();
+        let mut component = vec![NONE; n];
+        let mut counter = 0;
+        let mut components = 0;
+        for root in 0..n {
+            if index[root] != NONE {
+                continue;
+            }
+            index[root] = counter;
+            low[root] = counter;
+            counter += 1;
+            stack.push(root);
+            on_stack[root] = true;
+            let mut calls = vec![(root, 0)];
+            while let Some((v, next)) = calls.last_mut() {
+                let v = *v;
+                if *next < 2 {
+                    let w = edges[v][*next];
+                    *next += 1;
+                    if w == NONE {
+                        continue;
                     }
-                },
-                '|' => match current.2 {
-                    Direction::Left | Direction::Right => {
-                        queue.push_back((
-                            current.0.wrapping_add_signed(-1),
-                            current.1,
-                            Direction::Up,
-                        ));
-                        queue.push_back((current.0.wrapping_add(1), current.1, Direction::Down));
+                    if index[w] == NONE {
+                        index[w] = counter;
+                        low[w] = counter;
+                        counter += 1;
+                        stack.push(w);
+                        on_stack[w] = true;
+                        calls.push((w, 0));
+                    } else if on_stack[w] {
+                        low[v] = low[v].min(index[w]);
                     }
-                    Direction::Up | Direction::Down => {
-                        let delta = current.2.delta();
-                        let new_row = current.0.wrapping_add_signed(delta.0);
-                        let new_column = current.1.wrapping_add_signed(delta.1);
-                        queue.push_back((new_row, new_column, current.2));
+                    continue;
+                }
+                calls.pop();
+                if let Some(&(u, _)) = calls.last() {
+                    low[u] = low[u].min(low[v]);
+                }
+                if low[v] == index[v] {
+                    while let Some(w) = stack.pop() {
+                        on_stack[w] = false;
+                        component[w] = components;
+                        if w == v {
+                            break;
+                        }
                     }
-                },
-                _ => return Err(AoCError::from("unknown tile")),
+                    components += 1;
+                }
             }
         }
+        (component, components)
+    }
+
+    fn best_entry(&self) -> Result<(Beam, usize), AoCError> {
+        let edges = (0..self.tiles.len() * 4)
+            .map(|state| {
+                let mut next = [NONE; 2];
+                for (slot, beam) in next.iter_mut().zip(self.successors(self.beam(state))) {
+                    *slot = self.state(beam);
+                }
+                next
+            })
+            .collect::<Vec<[usize; 2]>>();
+        let (component, components) = self.components(&edges);
+
+        let mut members = vec![Vec::new(); components];
+        for (state, &c) in component.iter().enumerate() {
+            members[c].push(state);
+        }
+        let mut children = members
+            .iter()
+            .enumerate()
+            .map(|(c, states)| {
+                let mut next = states
+                    .iter()
+                    .flat_map(|&state| edges[state])
+                    .filter(|&w| w != NONE && component[w] != c)
+                    .map(|w| component[w])
+                    .collect::<Vec<usize>>();
+                next.sort_unstable();
+                next.dedup();
+                next
+            })
+            .collect::<Vec<Vec<usize>>>();
+        let mut references = vec![0usize; components];
+        children.iter().flatten().for_each(|&c| references[c] += 1);
+
+        let words = self.tiles.len().div_ceil(64);
+        let mut tiles = vec![None::<Vec<u64>>; components];
+        let mut counts = vec![0u32; components];
+        for c in 0..components {
+            let mut bits = None;
+            for child in core::mem::take(&mut children[c]) {
+                references[child] -= 1;
+                let child_bits = if references[child] == 0 {
+                    tiles[child].take()
+                } else {
+                    tiles[child].clone()
+                }
+                .ok_or(AoCError::from("component processed out of order"))?;
+                match bits.as_mut() {
+                    None => bits = Some(child_bits),
+                    Some(bits) => bits
+                        .iter_mut()
+                        .zip(child_bits)
+                        .for_each(|(word, other)| *word |= other),
+                }
+            }
+            let mut bits = bits.unwrap_or_else(|| vec![0u64; words]);
+            for &state in &members[c] {
+                bits[state / 256] |= 1 << (state / 4 % 64);
+            }
+            counts[c] = bits.iter().map(|word| word.count_ones()).sum();
+            if references[c] > 0 {
+                tiles[c] = Some(bits);
+            }
+        }
+
+        self.entries()
+            .into_iter()
+            .map(|entry| (entry, counts[component[self.state(entry)]] as usize))
+            .reduce(|best, entry| if entry.1 > best.1 { entry } else { best })
+            .ok_or(AoCError::from("no path found"))
     }
-    Ok(visited)
 }
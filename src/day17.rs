@@ -1,109 +1,289 @@
 use crate::error::AoCError;
-use alloc::collections::{BTreeMap, BinaryHeap};
+use crate::utils::parse_options;
+use alloc::collections::BTreeMap;
+use alloc::format;
 use alloc::string::{String, ToString};
+use alloc::vec;
 use alloc::vec::Vec;
-use core::cmp::Reverse;
+
+const OPTIONS: &[&str] = &[
+    "start",
+    "goal",
+    "min_run",
+    "max_run",
+    "can_reverse",
+    "turn_cost",
+    "movement",
+];
+const NONE: usize = usize::MAX;
+
+const ORTHOGONAL: [(isize, isize, char); 4] =
+    [(0, 1, '>'), (1, 0, 'v'), (0, -1, '<'), (-1, 0, '^')];
+const DIAGONAL: [(isize, isize, char); 4] =
+    [(1, 1, '↘'), (1, -1, '↙'), (-1, -1, '↖'), (-1, 1, '↗')];
+
+struct CrucibleRules {
+    min_run: usize,
+    max_run: usize,
+    can_reverse: bool,
+    turn_cost: u32,
+    directions: Vec<(isize, isize, char)>,
+}
+
+struct City {
+    losses: Vec<u32>,
+    width: usize,
+    height: usize,
+    start: (usize, usize),
+    goal: (usize, usize),
+}
+
+struct Route {
+    loss: u32,
+    path: Vec<(usize, usize, usize)>,
+}
 
 pub(crate) fn part1(input: String) -> Result<String, AoCError> {
-    solve(
-        input,
-        |direction, cnt| {
-            let mut new_directions = Vec::new();
-            if cnt < 3 {
-                new_directions.push((direction, cnt + 1));
-            }
-            new_directions.push(((direction.1, -direction.0), 1));
-            new_directions.push(((-direction.1, direction.0), 1));
-            new_directions
-        },
-        None,
-    )
+    let (options, city) = parse_city(&input)?;
+    let rules = CrucibleRules::new(1, 3, &options)?;
+    Ok(city.dijkstra(&rules)?.loss.to_string())
 }
 
 pub(crate) fn part2(input: String) -> Result<String, AoCError> {
-    solve(
-        input,
-        |direction, cnt| {
-            let mut new_directions = Vec::new();
-            if cnt < 10 {
-                new_directions.push((direction, cnt + 1));
-            }
-            if cnt >= 4 {
-                new_directions.push(((direction.1, -direction.0), 1));
-                new_directions.push(((-direction.1, direction.0), 1));
+    let (options, city) = parse_city(&input)?;
+    let rules = CrucibleRules::new(4, 10, &options)?;
+    Ok(city.dijkstra(&rules)?.loss.to_string())
+}
+
+pub(crate) fn details(input: String) -> Result<String, AoCError> {
+    let (options, city) = parse_city(&input)?;
+    let mut lines = Vec::new();
+    for (name, min_run, max_run) in [("Crucible", 1, 3), ("Ultra crucible", 4, 10)] {
+        let rules = CrucibleRules::new(min_run, max_run, &options)?;
+        match city.dijkstra(&rules) {
+            Ok(route) => {
+                lines.push(format!("{}: heat loss {}", name, route.loss));
+                lines.push(city.render(&rules, &route));
             }
-            new_directions
-        },
-        Some(|key| key.2 >= 4),
-    )
+            Err(error) => lines.push(format!("{}: {}", name, String::from(error))),
+        }
+        lines.push(String::new());
+    }
+    Ok(lines.join("\n").trim_end().to_string())
 }
 
-fn solve<F: Fn((isize, isize), u8) -> Vec<((isize, isize), u8)>>(
-    input: String,
-    new_directions: F,
-    result_predicate: Option<fn(((usize, usize), (isize, isize), u8)) -> bool>,
-) -> Result<String, AoCError> {
-    let map = input
-        .trim()
-        .lines()
-        .map(|line| {
-            Ok(line
-                .chars()
-                .map(|c| c.to_digit(10).ok_or(AoCError::from("not a digit")))
-                .collect::<Result<Vec<_>, _>>()?)
-        })
-        .collect::<Result<Vec<_>, AoCError>>()?;
-
-    let n = map.len();
-    let m = map[0].len();
-    let mut losses = BTreeMap::new();
-
-    dijkstra(&map, &mut losses, new_directions);
-
-    let res = losses
-        .into_iter()
-        .filter(|(key, _)| key.0 == (n - 1, m - 1))
-        .filter_map(|(key, value)| {
-            result_predicate
-                .into_iter()
-                .all(|f| f(key))
-                .then_some(value)
-        })
-        .min()
-        .unwrap_or(u32::MAX);
-    Ok(res.to_string())
+fn parse_cell(cell: &str, width: usize, height: usize) -> Result<(usize, usize), AoCError> {
+    let (row, column) = cell
+        .split_once(' ')
+        .ok_or(AoCError::from("cell should be row and column"))?;
+    let row = row.parse::<usize>()?;
+    let column = column.parse::<usize>()?;
+    if row == 0 || row > height || column == 0 || column > width {
+        return Err(AoCError::from("cell outside city"));
+    }
+    Ok((row - 1, column - 1))
+}
+
+fn parse_city(input: &str) -> Result<(BTreeMap<&str, &str>, City), AoCError> {
+    let (options, input) = parse_options(input, OPTIONS)?;
+    let lines = input.trim().lines().collect::<Vec<&str>>();
+    let width = lines.first().map_or(0, |l| l.len());
+    let height = lines.len();
+    if width == 0 {
+        return Err(AoCError::from("empty city"));
+    }
+    if lines.iter().any(|l| l.len() != width) {
+        return Err(AoCError::from("ragged city"));
+    }
+    let losses = lines
+        .iter()
+        .flat_map(|l| l.chars())
+        .map(|c| c.to_digit(10).ok_or(AoCError::from("not a digit")))
+        .collect::<Result<Vec<u32>, AoCError>>()?;
+    let start = match options.get("start") {
+        Some(cell) => parse_cell(cell, width, height)?,
+        None => (0, 0),
+    };
+    let goal = match options.get("goal") {
+        Some(cell) => parse_cell(cell, width, height)?,
+        None => (height - 1, width - 1),
+    };
+    let city = City {
+        losses,
+        width,
+        height,
+        start,
+        goal,
+    };
+    Ok((options, city))
 }
 
-fn dijkstra<F: Fn((isize, isize), u8) -> Vec<((isize, isize), u8)>>(
-    map: &Vec<Vec<u32>>,
-    losses: &mut BTreeMap<((usize, usize), (isize, isize), u8), u32>,
-    new_directions: F,
-) {
-    let mut queue = BinaryHeap::<Reverse<(u32, (usize, usize), (isize, isize), u8)>>::from([
-        Reverse((0, (0, 0), (0, 1), 0)),
-        Reverse((0, (0, 0), (1, 0), 0)),
-    ]);
-    while !queue.is_empty() {
-        let (path_loss, position, direction, cnt) = queue.pop().unwrap().0;
-        if losses.contains_key(&(position, direction, cnt)) {
-            continue;
+impl CrucibleRules {
+    fn new(
+        min_run: usize,
+        max_run: usize,
+        options: &BTreeMap<&str, &str>,
+    ) -> Result<CrucibleRules, AoCError> {
+        let rules = CrucibleRules {
+            min_run: options
+                .get("min_run")
+                .map_or(Ok(min_run), |v| v.parse::<usize>())?,
+            max_run: options
+                .get("max_run")
+                .map_or(Ok(max_run), |v| v.parse::<usize>())?,
+            can_reverse: options
+                .get("can_reverse")
+                .map_or(Ok(false), |v| v.parse::<bool>())?,
+            turn_cost: options
+                .get("turn_cost")
+                .map_or(Ok(0), |v| v.parse::<u32>())?,
+            directions: match options.get("movement").copied().unwrap_or("orthogonal") {
+                "orthogonal" => ORTHOGONAL.to_vec(),
+                "diagonal" => DIAGONAL.to_vec(),
+                "8-way" => ORTHOGONAL.into_iter().chain(DIAGONAL).collect(),
+                _ => return Err(AoCError::from("unknown movement")),
+            },
+        };
+        if rules.min_run == 0 {
+            return Err(AoCError::from("min_run should be positive"));
+        }
+        if rules.min_run > rules.max_run {
+            return Err(AoCError::from("min_run should not exceed max_run"));
         }
-        losses.insert((position, direction, cnt), path_loss);
-
-        new_directions(direction, cnt)
-            .into_iter()
-            .for_each(|(new_direction, new_cnt)| {
-                let new_row = position.0.wrapping_add_signed(new_direction.0);
-                let new_column = position.1.wrapping_add_signed(new_direction.1);
-                if let Some(&loss) = map.get(new_row).and_then(|row| row.get(new_column)) {
-                    let new_losses = path_loss + loss;
-                    queue.push(Reverse((
-                        new_losses,
-                        (new_row, new_column),
-                        new_direction,
-                        new_cnt,
-                    )));
+        if rules.turn_cost > 1000 {
+            return Err(AoCError::from("turn_cost too large"));
+        }
+        Ok(rules)
+    }
+
+    fn opposite(&self, direction: usize) -> Option<usize> {
+        let (dr, dc, _) = self.directions[direction];
+        self.directions
+            .iter()
+            .position(|&(r, c, _)| (r, c) == (-dr, -dc))
+    }
+}
+
+impl City {
+    fn dijkstra(&self, rules: &CrucibleRules) -> Result<Route, AoCError> {
+        let CrucibleRules {
+            min_run, max_run, ..
+        } = *rules;
+        let directions = rules.directions.len();
+        let runs = max_run
+            .checked_add(1)
+            .ok_or(AoCError::from("too many states"))?;
+        let index = |(row, column): (usize, usize), direction: usize, run: usize| {
+            ((row * self.width + column) * directions + direction) * runs + run
+        };
+        let decode = |state: usize| {
+            let cell = state / runs / directions;
+            (
+                cell / self.width,
+                cell % self.width,
+                state / runs % directions,
+                state % runs,
+            )
+        };
+        let states = self
+            .losses
+            .len()
+            .checked_mul(directions)
+            .and_then(|states| states.checked_mul(runs))
+            .filter(|&states| states < 1 << 24)
+            .ok_or(AoCError::from("too many states"))?;
+        let opposite = (0..directions)
+            .map(|direction| rules.opposite(direction))
+            .collect::<Vec<Option<usize>>>();
+        let mut losses = vec![u32::MAX; states + 1];
+        let mut previous = vec![NONE; states + 1];
+        let slots = 10 + rules.turn_cost as usize;
+        let mut buckets = vec![Vec::new(); slots];
+        let mut pending = 1;
+        losses[states] = 0;
+        buckets[0].push(states);
+
+        let mut loss = 0;
+        while pending > 0 {
+            let bucket = loss as usize % slots;
+            while let Some(state) = buckets[bucket].pop() {
+                pending -= 1;
+                if losses[state] != loss {
+                    continue;
                 }
-            });
+                let (row, column, direction, run) = if state == states {
+                    (self.start.0, self.start.1, 0, 0)
+                } else {
+                    decode(state)
+                };
+                for (next, &(dr, dc, _)) in rules.directions.iter().enumerate() {
+                    let (next_run, cost) = if state == states {
+                        (1, 0)
+                    } else if next == direction {
+                        (run + 1, 0)
+                    } else if run < min_run
+                        || (!rules.can_reverse && opposite[direction] == Some(next))
+                    {
+                        continue;
+                    } else {
+                        (1, rules.turn_cost)
+                    };
+                    let row = row.wrapping_add_signed(dr);
+                    let column = column.wrapping_add_signed(dc);
+                    if next_run > max_run || row >= self.height || column >= self.width {
+                        continue;
+                    }
+                    let target = index((row, column), next, next_run);
+                    let next_loss = loss + cost + self.losses[row * self.width + column];
+                    if next_loss < losses[target] {
+                        losses[target] = next_loss;
+                        previous[target] = state;
+                        buckets[next_loss as usize % slots].push(target);
+                        pending += 1;
+                    }
+                }
+            }
+            loss += 1;
+        }
+
+        let goal = if self.goal == self.start {
+            states
+        } else {
+            (0..directions)
+                .flat_map(|direction| {
+                    (min_run..=max_run).map(move |run| index(self.goal, direction, run))
+                })
+                .filter(|&state| losses[state] != u32::MAX)
+                .min_by_key(|&state| losses[state])
+                .ok_or(AoCError::from("no route"))?
+        };
+        let mut path = Vec::new();
+        let mut state = goal;
+        while state != states {
+            let (row, column, direction, _) = decode(state);
+            path.push((row, column, direction));
+            state = previous[state];
+        }
+        path.reverse();
+        Ok(Route {
+            loss: losses[goal],
+            path,
+        })
+    }
+
+    fn render(&self, rules: &CrucibleRules, route: &Route) -> String {
+        let mut tiles = self
+            .losses
+            .iter()
+            .map(|&loss| char::from_digit(loss, 10).unwrap_or('?'))
+            .collect::<Vec<char>>();
+        for &(row, column, direction) in &route.path {
+            tiles[row * self.width + column] = rules.directions[direction].2;
+        }
+        tiles
+            .chunks(self.width)
+            .map(|line| line.iter().collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n")
     }
 }
@@ -1,73 +1,374 @@
 use crate::error::AoCError;
+use crate::utils::parse_options;
+use alloc::format;
 use alloc::string::{String, ToString};
+use alloc::vec;
 use alloc::vec::Vec;
 
+const OPTIONS: &[&str] = &["close", "render", "plan"];
+const MAX_SIZE: i64 = 1000;
+const INTERIOR: &str = "c0c0c0";
+const OUTSIDE: &str = "ffffff";
+
+type Parser = fn(&str) -> Result<Step<'_>, AoCError>;
+
+#[derive(Copy, Clone)]
+struct Step<'a> {
+    direction: (i64, i64),
+    length: i64,
+    color: &'a str,
+}
+
+struct Lagoon<'a> {
+    steps: Vec<Step<'a>>,
+    vertices: Vec<(i64, i64)>,
+    warnings: Vec<String>,
+}
+
 pub(crate) fn part1(input: String) -> Result<String, AoCError> {
-    solve(input, |instruction| {
-        let (direction, rest) = instruction
-            .split_once(' ')
-            .ok_or(AoCError::from("no direction"))?;
-        let (count, _) = rest.split_once(' ').ok_or(AoCError::from("no count"))?;
-        let count = count.parse::<isize>()?;
-        let direction = match direction {
-            "U" => Ok((-1, 0)),
-            "D" => Ok((1, 0)),
-            "L" => Ok((0, -1)),
-            "R" => Ok((0, 1)),
-            _ => Err(AoCError::from("unknown direction")),
-        }?;
-        Ok((direction, count))
-    })
+    Ok(dig(&input, parse_plain)?.total().to_string())
 }
 
 pub(crate) fn part2(input: String) -> Result<String, AoCError> {
-    solve(input, |instruction| {
-        let (_, instruction) = instruction
-            .rsplit_once(' ')
-            .ok_or(AoCError::from("no instruction"))?;
-        let instruction = instruction.trim_matches(['(', ')', '#'].as_slice());
-        let count = isize::from_str_radix(&instruction[..5], 16)?;
-        let direction = match &instruction[5..] {
-            "0" => Ok((0, 1)),
-            "1" => Ok((1, 0)),
-            "2" => Ok((0, -1)),
-            "3" => Ok((-1, 0)),
-            _ => Err(AoCError::from("unknown direction")),
-        }?;
-        Ok((direction, count))
+    Ok(dig(&input, parse_hex)?.total().to_string())
+}
+
+pub(crate) fn details(input: String) -> Result<String, AoCError> {
+    let (options, _) = parse_options(&input, OPTIONS)?;
+    if let Some(&format) = options.get("render") {
+        let parse_step = match options.get("plan").copied().unwrap_or("1") {
+            "1" => parse_plain as Parser,
+            "2" => parse_hex,
+            _ => return Err(AoCError::from("plan should be 1 or 2")),
+        };
+        let (width, height, pixels) = dig(&input, parse_step)?.rasterize();
+        return match format {
+            "svg" => Ok(svg(width, height, &pixels)),
+            "ppm" => Ok(ppm(width, height, &pixels)),
+            _ => Err(AoCError::from("unknown render format")),
+        };
+    }
+    let mut lines = Vec::new();
+    for (name, parse_step) in [("Part 1", parse_plain as Parser), ("Part 2", parse_hex)] {
+        match dig(&input, parse_step) {
+            Ok(lagoon) => {
+                let (top, left, bottom, right) = lagoon.bounding_box();
+                lines.push(format!("{}: {} steps", name, lagoon.steps.len()));
+                lines.extend(lagoon.warnings.iter().map(|w| format!("  warning: {}", w)));
+                lines.push(format!(
+                    "  bounding box: rows {}..={}, columns {}..={} ({}x{})",
+                    top,
+                    bottom,
+                    left,
+                    right,
+                    right - left + 1,
+                    bottom - top + 1
+                ));
+                lines.push(format!("  perimeter: {}", lagoon.perimeter()));
+                lines.push(format!("  interior: {}", lagoon.interior()));
+                lines.push(format!("  total: {}", lagoon.total()));
+            }
+            Err(error) => lines.push(format!("{}: {}", name, String::from(error))),
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+fn parse_color(color: &str) -> Result<&str, AoCError> {
+    let color = color
+        .strip_prefix("(#")
+        .and_then(|c| c.strip_suffix(')'))
+        .filter(|c| c.len() == 6 && c.bytes().all(|b| b.is_ascii_hexdigit()))
+        .ok_or(AoCError::from("invalid color"))?;
+    Ok(color)
+}
+
+fn parse_plain(instruction: &str) -> Result<Step<'_>, AoCError> {
+    let (direction, rest) = instruction
+        .split_once(' ')
+        .ok_or(AoCError::from("no direction"))?;
+    let (length, color) = rest.split_once(' ').ok_or(AoCError::from("no count"))?;
+    let direction = match direction {
+        "U" => Ok((-1, 0)),
+        "D" => Ok((1, 0)),
+        "L" => Ok((0, -1)),
+        "R" => Ok((0, 1)),
+        _ => Err(AoCError::from("unknown direction")),
+    }?;
+    Ok(Step {
+        direction,
+        length: length.parse::<i64>()?,
+        color: parse_color(color)?,
     })
 }
 
-fn solve<F: Fn(&str) -> Result<((isize, isize), isize), AoCError>>(
-    input: String,
-    parse_instruction: F,
-) -> Result<String, AoCError> {
-    let mut current_row = 0;
-    let mut current_column = 0;
-    let mut map = input
+fn parse_hex(instruction: &str) -> Result<Step<'_>, AoCError> {
+    let (_, color) = instruction
+        .rsplit_once(' ')
+        .ok_or(AoCError::from("no instruction"))?;
+    let color = parse_color(color)?;
+    let direction = match &color[5..] {
+        "0" => Ok((0, 1)),
+        "1" => Ok((1, 0)),
+        "2" => Ok((0, -1)),
+        "3" => Ok((-1, 0)),
+        _ => Err(AoCError::from("unknown direction")),
+    }?;
+    Ok(Step {
+        direction,
+        length: i64::from_str_radix(&color[..5], 16)?,
+        color,
+    })
+}
+
+fn dig(input: &str, parse_step: Parser) -> Result<Lagoon<'_>, AoCError> {
+    let (options, input) = parse_options(input, OPTIONS)?;
+    let implicit = match options.get("close").copied().unwrap_or("reject") {
+        "reject" => false,
+        "implicit" => true,
+        _ => return Err(AoCError::from("unknown close mode")),
+    };
+    let steps = input
         .trim()
         .lines()
-        .map(|line| {
-            let (direction, count) = parse_instruction(line)?;
-            current_row += (direction.0 * count) as i64;
-            current_column += (direction.1 * count) as i64;
-            Ok((current_row, current_column))
-        })
-        .collect::<Result<Vec<(i64, i64)>, AoCError>>()?;
-    map.insert(0, (0, 0));
-
-    let border = map
-        .windows(2)
-        .fold(0, |a, c| {
-            a + (c[0].0 - c[1].0).abs() + (c[0].1 - c[1].1).abs()
-        })
-        .abs();
-    let area = map
-        .windows(2)
-        .fold(0, |a, c| a + c[0].0 * c[1].1 - c[0].1 * c[1].0)
-        .abs()
-        >> 1;
-    let interior = area - (border >> 1) + 1;
-
-    Ok((border + interior).to_string())
+        .map(parse_step)
+        .collect::<Result<Vec<Step>, AoCError>>()?;
+    if steps.iter().any(|step| step.length <= 0) {
+        return Err(AoCError::from("steps should have positive length"));
+    }
+
+    let (row, column) = steps.iter().fold((0, 0), |(row, column), step| {
+        (
+            row + step.direction.0 * step.length,
+            column + step.direction.1 * step.length,
+        )
+    });
+    if (row, column) == (0, 0) {
+        return Lagoon::new(steps, Vec::new());
+    }
+    if !implicit {
+        return Err(AoCError::from(format!(
+            "plan ends at row {}, column {} instead of the start",
+            row, column
+        )));
+    }
+
+    let vertical = ((-row.signum(), 0), row.abs());
+    let horizontal = ((0, -column.signum()), column.abs());
+    let close = |legs: [((i64, i64), i64); 2]| {
+        let closing = legs
+            .into_iter()
+            .filter(|&(_, length)| length > 0)
+            .map(|(direction, length)| Step {
+                direction,
+                length,
+                color: "000000",
+            })
+            .collect::<Vec<Step>>();
+        let warning = format!(
+            "plan ends at row {}, column {}; closed with {} extra steps",
+            row,
+            column,
+            closing.len()
+        );
+        Lagoon::new(
+            steps.iter().copied().chain(closing).collect(),
+            vec![warning],
+        )
+    };
+    let (first, second) = if steps.last().is_some_and(|step| step.direction.1 == 0) {
+        ([horizontal, vertical], [vertical, horizontal])
+    } else {
+        ([vertical, horizontal], [horizontal, vertical])
+    };
+    close(first).or_else(|error| close(second).map_err(|_| error))
+}
+
+impl<'a> Lagoon<'a> {
+    fn new(steps: Vec<Step<'a>>, warnings: Vec<String>) -> Result<Lagoon<'a>, AoCError> {
+        let vertices = steps
+            .iter()
+            .scan((0, 0), |(row, column), step| {
+                *row += step.direction.0 * step.length;
+                *column += step.direction.1 * step.length;
+                Some((*row, *column))
+            })
+            .collect::<Vec<(i64, i64)>>();
+        let lagoon = Lagoon {
+            steps,
+            vertices: [(0, 0)].into_iter().chain(vertices).collect(),
+            warnings,
+        };
+        lagoon.check_intersections()?;
+        Ok(lagoon)
+    }
+
+    fn check_intersections(&self) -> Result<(), AoCError> {
+        let n = self.steps.len();
+        let bounds = self
+            .vertices
+            .windows(2)
+            .map(|v| {
+                (
+                    v[0].0.min(v[1].0),
+                    v[0].1.min(v[1].1),
+                    v[0].0.max(v[1].0),
+                    v[0].1.max(v[1].1),
+                )
+            })
+            .collect::<Vec<(i64, i64, i64, i64)>>();
+        for i in 0..n {
+            for j in i + 1..n {
+                let adjacent = j == i + 1 || (i == 0 && j == n - 1);
+                let intersects = if adjacent {
+                    let (a, b) = if j == i + 1 { (i, j) } else { (j, i) };
+                    let (da, db) = (self.steps[a].direction, self.steps[b].direction);
+                    (da.0 + db.0, da.1 + db.1) == (0, 0)
+                } else {
+                    let (a, b) = (bounds[i], bounds[j]);
+                    a.0 <= b.2 && b.0 <= a.2 && a.1 <= b.3 && b.1 <= a.3
+                };
+                if intersects {
+                    return Err(AoCError::from(format!(
+                        "plan intersects itself at steps {} and {}",
+                        i + 1,
+                        j + 1
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn bounding_box(&self) -> (i64, i64, i64, i64) {
+        self.vertices.iter().fold(
+            (0, 0, 0, 0),
+            |(top, left, bottom, right), &(row, column)| {
+                (
+                    top.min(row),
+                    left.min(column),
+                    bottom.max(row),
+                    right.max(column),
+                )
+            },
+        )
+    }
+
+    fn perimeter(&self) -> i64 {
+        self.steps.iter().map(|step| step.length).sum()
+    }
+
+    fn interior(&self) -> i64 {
+        let area = self
+            .vertices
+            .windows(2)
+            .fold(0, |a, c| a + c[0].0 * c[1].1 - c[0].1 * c[1].0)
+            .abs()
+            >> 1;
+        area - (self.perimeter() >> 1) + 1
+    }
+
+    fn total(&self) -> i64 {
+        self.perimeter() + self.interior()
+    }
+
+    fn rasterize(&self) -> (usize, usize, Vec<&'a str>) {
+        let (top, left, bottom, right) = self.bounding_box();
+        let rows = breakpoints(self.vertices.iter().map(|v| v.0), top, bottom);
+        let columns = breakpoints(self.vertices.iter().map(|v| v.1), left, right);
+        let (height, width) = (rows.len() - 1, columns.len() - 1);
+        let row_index = |row: i64| rows.partition_point(|&r| r < row);
+        let column_index = |column: i64| columns.partition_point(|&c| c < column);
+
+        let mut pixels = vec![OUTSIDE; width * height];
+        let mut trench = vec![false; width * height];
+        for (step, v) in self.steps.iter().zip(self.vertices.windows(2)) {
+            let (r0, r1) = (row_index(v[0].0.min(v[1].0)), row_index(v[0].0.max(v[1].0)));
+            let (c0, c1) = (
+                column_index(v[0].1.min(v[1].1)),
+                column_index(v[0].1.max(v[1].1)),
+            );
+            for row in r0..=r1 {
+                for column in c0..=c1 {
+                    pixels[row * width + column] = step.color;
+                    trench[row * width + column] = true;
+                }
+            }
+        }
+
+        let mut verticals = self
+            .vertices
+            .windows(2)
+            .filter(|v| v[0].1 == v[1].1)
+            .map(|v| (v[0].1, v[0].0.min(v[1].0), v[0].0.max(v[1].0)))
+            .collect::<Vec<(i64, i64, i64)>>();
+        verticals.sort_unstable();
+        for (row, &r) in rows[..height].iter().enumerate() {
+            let mut crossings = verticals
+                .iter()
+                .filter(|&&(_, from, to)| from <= r && r < to)
+                .map(|&(column, _, _)| column)
+                .peekable();
+            let mut inside = false;
+            for (column, &c) in columns[..width].iter().enumerate() {
+                while crossings.next_if(|&x| x < c).is_some() {
+                    inside = !inside;
+                }
+                if inside && !trench[row * width + column] {
+                    pixels[row * width + column] = INTERIOR;
+                }
+            }
+        }
+        (width, height, pixels)
+    }
+}
+
+fn breakpoints<I: Iterator<Item = i64>>(coordinates: I, from: i64, to: i64) -> Vec<i64> {
+    if to - from < MAX_SIZE {
+        return (from..=to + 1).collect();
+    }
+    let mut breakpoints = coordinates.flat_map(|c| [c, c + 1]).collect::<Vec<i64>>();
+    breakpoints.sort_unstable();
+    breakpoints.dedup();
+    breakpoints
+}
+
+fn svg(width: usize, height: usize, pixels: &[&str]) -> String {
+    let mut image = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {} {}\" shape-rendering=\"crispEdges\">",
+        width, height
+    );
+    for (row, line) in pixels.chunks(width).enumerate() {
+        let mut column = 0;
+        for run in line.chunk_by(|a, b| a == b) {
+            if run[0] != OUTSIDE {
+                image.push_str(&format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"1\" fill=\"#{}\"/>",
+                    column,
+                    row,
+                    run.len(),
+                    run[0]
+                ));
+            }
+            column += run.len();
+        }
+    }
+    image.push_str("</svg>");
+    image
+}
+
+fn ppm(width: usize, height: usize, pixels: &[&str]) -> String {
+    let mut image = format!("P3\n{} {}\n255", width, height);
+    for line in pixels.chunks(width) {
+        let line = line
+            .iter()
+            .map(|color| {
+                let value = u32::from_str_radix(color, 16).unwrap_or(0);
+                format!("{} {} {}", value >> 16, (value >> 8) & 255, value & 255)
+            })
+            .collect::<Vec<String>>();
+        image.push('\n');
+        image.push_str(&line.join(" "));
+    }
+    image
 }
@@ -1,70 +1,225 @@
 use crate::error::AoCError;
-use alloc::collections::BTreeMap;
+use crate::utils::parse_options;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
-use core::ops::RangeInclusive;
+
+const OPTIONS: &[&str] = &["range", "bounds"];
+const ACCEPT: usize = 0;
+const REJECT: usize = 1;
+
+type Cuboid = Vec<(i64, i64)>;
+type Part<'a> = Vec<(&'a str, i64)>;
+
+struct Universe<'a> {
+    attributes: Vec<&'a str>,
+    bounds: Cuboid,
+    known: usize,
+}
 
 struct Condition<'a> {
     key: &'a str,
+    less: bool,
     value: i64,
-    valid: Option<i64>,
 }
 
-impl<'a> Condition<'a> {
-    fn check(&self, part: &BTreeMap<&str, i64>) -> bool {
-        part.get(self.key).iter().all(|&&v| self.check_point(v))
-    }
-    fn check_point(&self, p: i64) -> bool {
-        self.valid
-            .iter()
-            .all(|&valid| (p - self.value).signum() == valid)
-    }
+struct Rule<'a> {
+    condition: Option<Condition<'a>>,
+    target: &'a str,
 }
 
-pub(crate) fn part1(input: String) -> Result<String, AoCError> {
-    let (workflows, parts) = input
-        .trim()
-        .split_once("\n\n")
-        .ok_or(AoCError::from("invalid format"))?;
-    let workflows = parse_workflows(workflows)?;
-    let parts = parse_parts(parts)?;
+type Workflows<'a> = BTreeMap<&'a str, Vec<Rule<'a>>>;
 
-    let s = parts
+enum Node {
+    Accept,
+    Reject,
+    Split {
+        attribute: usize,
+        threshold: i64,
+        low: usize,
+        high: usize,
+    },
+}
+
+#[derive(PartialEq)]
+enum Severity {
+    Error,
+    Warning,
+}
+
+struct Diagnostic {
+    severity: Severity,
+    message: String,
+}
+
+struct Compiler<'w, 'a> {
+    workflows: &'w Workflows<'a>,
+    universe: &'w Universe<'a>,
+    nodes: Vec<Node>,
+    entries: BTreeMap<&'a str, Option<usize>>,
+}
+
+pub(crate) fn part1(input: String) -> Result<String, AoCError> {
+    let (workflows, parts, mut universe) = parse_system(&input)?;
+    let parts = parts
         .into_iter()
         .map(|part| {
-            let mut workflow_name = "in";
-            while workflow_name != "R" && workflow_name != "A" {
-                let workflow = workflows
-                    .get(workflow_name)
-                    .ok_or(AoCError::from("unknown workflow"))?;
-                workflow_name = workflow
-                    .iter()
-                    .find_map(|(condition, next_workflow)| {
-                        condition.check(&part).then_some(next_workflow)
-                    })
-                    .ok_or(AoCError::from("unknown transition"))?;
-            }
-            if workflow_name == "A" {
-                Ok(part.values().sum::<i64>())
-            } else {
-                Ok(0)
-            }
+            universe
+                .attributes
+                .iter()
+                .map(|&attribute| {
+                    part.iter()
+                        .find(|(name, _)| *name == attribute)
+                        .map(|&(_, value)| value)
+                        .ok_or(AoCError::from(format!("part without {}", attribute)))
+                })
+                .collect::<Result<Vec<i64>, AoCError>>()
+        })
+        .collect::<Result<Vec<Vec<i64>>, AoCError>>()?;
+    for values in &parts {
+        for (range, &v) in universe.bounds.iter_mut().zip(values) {
+            *range = (range.0.min(v), range.1.max(v));
+        }
+    }
+    check(&workflows, &universe)?;
+    let boxes = partition(&workflows, &universe)?;
+    let s = parts
+        .iter()
+        .map(|values| {
+            let &(_, accepted) = boxes
+                .iter()
+                .find(|(cuboid, _)| {
+                    cuboid
+                        .iter()
+                        .zip(values)
+                        .all(|(&(low, high), &v)| low <= v && v <= high)
+                })
+                .ok_or(AoCError::from("part outside attribute ranges"))?;
+            Ok(if accepted { values.iter().sum() } else { 0 })
         })
-        .collect::<Result<Vec<_>, AoCError>>()?;
-    let s = s.into_iter().sum::<i64>();
+        .collect::<Result<Vec<i64>, AoCError>>()?
+        .into_iter()
+        .sum::<i64>();
     Ok(s.to_string())
 }
 
 pub(crate) fn part2(input: String) -> Result<String, AoCError> {
-    let (workflows, _) = input
+    let (workflows, _, universe) = parse_system(&input)?;
+    check(&workflows, &universe)?;
+    let s = partition(&workflows, &universe)?
+        .iter()
+        .filter(|(_, accepted)| *accepted)
+        .try_fold(0u128, |s, (cuboid, _)| s.checked_add(volume(cuboid)?))
+        .ok_or(AoCError::from("overflow"))?;
+    Ok(s.to_string())
+}
+
+pub(crate) fn details(input: String) -> Result<String, AoCError> {
+    let (workflows, _, universe) = parse_system(&input)?;
+    let diagnostics = analyze(&workflows, &universe);
+    let mut lines = diagnostics
+        .iter()
+        .map(|d| d.to_string())
+        .collect::<Vec<String>>();
+    if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        return Ok(lines.join("\n"));
+    }
+    let boxes = partition(&workflows, &universe)?;
+    let accepted = boxes.iter().filter(|(_, accepted)| *accepted).count();
+    lines.push(format!(
+        "{} boxes: {} accepted, {} rejected",
+        boxes.len(),
+        accepted,
+        boxes.len() - accepted
+    ));
+    lines.extend(boxes.iter().map(|(cuboid, accepted)| {
+        let ranges = universe
+            .attributes
+            .iter()
+            .zip(cuboid)
+            .map(|(attribute, (low, high))| format!("{}={}..={}", attribute, low, high))
+            .collect::<Vec<String>>();
+        let volume = volume(cuboid).map_or(String::from("overflow"), |v| v.to_string());
+        format!(
+            "{} {} ({})",
+            if *accepted { 'A' } else { 'R' },
+            ranges.join(" "),
+            volume
+        )
+    }));
+    Ok(lines.join("\n"))
+}
+
+fn parse_system(input: &str) -> Result<(Workflows<'_>, Vec<Part<'_>>, Universe<'_>), AoCError> {
+    let (options, input) = parse_options(input, OPTIONS)?;
+    let (workflows, parts) = input
         .trim()
         .split_once("\n\n")
         .ok_or(AoCError::from("invalid format"))?;
     let workflows = parse_workflows(workflows)?;
-    Ok(dfs(&workflows)?.to_string())
+    let parts = parse_parts(parts)?;
+    let range = parse_range(options.get("range").copied().unwrap_or("1..=4000"))?;
+    let bounds = options
+        .get("bounds")
+        .into_iter()
+        .flat_map(|bounds| bounds.split(' '))
+        .map(|bound| {
+            let (attribute, range) = bound
+                .split_once('=')
+                .ok_or(AoCError::from("bounds should be attribute=low..=high"))?;
+            Ok((attribute, parse_range(range)?))
+        })
+        .collect::<Result<Vec<(&str, (i64, i64))>, AoCError>>()?;
+
+    let mut attributes = Vec::new();
+    let mut known = 0;
+    let declared = parts
+        .iter()
+        .flatten()
+        .map(|&(attribute, _)| attribute)
+        .chain(bounds.iter().map(|&(attribute, _)| attribute))
+        .map(|attribute| (attribute, true));
+    let conditions = workflows
+        .values()
+        .flatten()
+        .filter_map(|rule| rule.condition.as_ref())
+        .map(|condition| (condition.key, false));
+    for (attribute, declared) in declared.chain(conditions) {
+        if !attributes.contains(&attribute) {
+            attributes.push(attribute);
+            known += declared as usize;
+        }
+    }
+    let bounds = attributes
+        .iter()
+        .map(|attribute| {
+            bounds
+                .iter()
+                .find(|(name, _)| name == attribute)
+                .map_or(range, |&(_, range)| range)
+        })
+        .collect();
+    let universe = Universe {
+        attributes,
+        bounds,
+        known,
+    };
+    Ok((workflows, parts, universe))
+}
+
+fn parse_range(range: &str) -> Result<(i64, i64), AoCError> {
+    let (low, high) = range
+        .split_once("..=")
+        .ok_or(AoCError::from("range should be low..=high"))?;
+    let (low, high) = (low.parse::<i64>()?, high.parse::<i64>()?);
+    if low > high {
+        return Err(AoCError::from("empty range"));
+    }
+    Ok((low, high))
 }
 
-fn parse_workflows(s: &str) -> Result<BTreeMap<&str, Vec<(Condition, &str)>>, AoCError> {
+fn parse_workflows(s: &str) -> Result<Workflows<'_>, AoCError> {
     s.lines()
         .map(|line| {
             let (name, rules) = line
@@ -76,40 +231,28 @@ fn parse_workflows(s: &str) -> Result<BTreeMap<&str, Vec<(Condition, &str)>>, Ao
                 rules
                     .split(',')
                     .map(|rule| {
-                        if let Some((cond, next_name)) = rule.split_once(':') {
-                            if let Some((key, value)) = cond.split_once('<') {
-                                let value = value.parse::<i64>()?;
-                                Ok((
-                                    Condition {
-                                        key,
-                                        value,
-                                        valid: Some(-1),
-                                    },
-                                    next_name,
-                                ))
-                            } else if let Some((key, value)) = cond.split_once('>') {
-                                let value = value.parse::<i64>()?;
-                                Ok((
-                                    Condition {
-                                        key,
-                                        value,
-                                        valid: Some(1),
-                                    },
-                                    next_name,
-                                ))
+                        let Some((condition, target)) = rule.split_once(':') else {
+                            return Ok(Rule {
+                                condition: None,
+                                target: rule,
+                            });
+                        };
+                        let (key, less, value) =
+                            if let Some((key, value)) = condition.split_once('<') {
+                                (key, true, value)
+                            } else if let Some((key, value)) = condition.split_once('>') {
+                                (key, false, value)
                             } else {
-                                Err(AoCError::from("invalid condition"))
-                            }
-                        } else {
-                            Ok((
-                                Condition {
-                                    key: "",
-                                    value: 0,
-                                    valid: None,
-                                },
-                                rule,
-                            ))
+                                return Err(AoCError::from("invalid condition"));
+                            };
+                        let value = value.parse::<i64>()?;
+                        if value == i64::MIN || value == i64::MAX {
+                            return Err(AoCError::from("condition value out of range"));
                         }
+                        Ok(Rule {
+                            condition: Some(Condition { key, less, value }),
+                            target,
+                        })
                     })
                     .collect::<Result<Vec<_>, AoCError>>()?,
             ))
@@ -117,11 +260,10 @@ fn parse_workflows(s: &str) -> Result<BTreeMap<&str, Vec<(Condition, &str)>>, Ao
         .collect()
 }
 
-fn parse_parts(s: &str) -> Result<Vec<BTreeMap<&str, i64>>, AoCError> {
+fn parse_parts(s: &str) -> Result<Vec<Part<'_>>, AoCError> {
     s.lines()
         .map(|line| {
-            Ok(line
-                .trim_matches(['{', '}'].as_slice())
+            line.trim_matches(['{', '}'].as_slice())
                 .split(',')
                 .map(|cat| {
                     let (name, value) = cat
@@ -130,82 +272,279 @@ fn parse_parts(s: &str) -> Result<Vec<BTreeMap<&str, i64>>, AoCError> {
                     let value = value.parse::<i64>()?;
                     Ok((name, value))
                 })
-                .collect::<Result<BTreeMap<_, _>, AoCError>>()?)
+                .collect::<Result<Part, AoCError>>()
         })
         .collect()
 }
 
-fn dfs(workflows: &BTreeMap<&str, Vec<(Condition, &str)>>) -> Result<u64, AoCError> {
-    let mut answer = 0u64;
-    let state = (
-        "in",
-        BTreeMap::from([
-            ("x", RangeInclusive::new(1, 4000)),
-            ("m", RangeInclusive::new(1, 4000)),
-            ("a", RangeInclusive::new(1, 4000)),
-            ("s", RangeInclusive::new(1, 4000)),
-        ]),
-    );
-    let mut stack = Vec::from([state]);
-    while let Some((workflow, mut ranges)) = stack.pop() {
-        if workflow == "R" {
-            continue;
+impl<'a> Compiler<'_, 'a> {
+    fn compile(&mut self, name: &'a str) -> Result<usize, AoCError> {
+        match name {
+            "A" => return Ok(ACCEPT),
+            "R" => return Ok(REJECT),
+            _ => {}
         }
-        if workflow == "A" {
-            answer += ranges
-                .values()
-                .map(|r| r.end() - r.start() + 1)
-                .product::<i64>() as u64;
-            continue;
+        match self.entries.get(name) {
+            Some(Some(entry)) => return Ok(*entry),
+            Some(None) => return Err(AoCError::from(format!("cycle through {}", name))),
+            None => {}
         }
-        let workflow = workflows
-            .get(workflow)
-            .ok_or(AoCError::from("unknown workflow"))?;
-        for (condition, &ref next_workflow) in workflow {
-            if let Some(valid) = condition.valid {
-                let range = ranges
-                    .get(condition.key)
-                    .ok_or(AoCError::from("invalid attribute"))?;
-                if range.contains(&condition.value) {
-                    let left;
-                    let right;
-                    if valid < 0 {
-                        left = RangeInclusive::new(*range.start(), condition.value - 1);
-                        right = RangeInclusive::new(condition.value, *range.end());
+        self.entries.insert(name, None);
+        let rules = self
+            .workflows
+            .get(name)
+            .ok_or(AoCError::from(format!("unknown workflow {}", name)))?;
+        if rules.last().is_some_and(|rule| rule.condition.is_some()) {
+            return Err(AoCError::from(format!("{} has no fallback rule", name)));
+        }
+        let mut next = None;
+        let live = liveness(rules, self.universe);
+        for (rule, _) in rules.iter().zip(live).rev().filter(|(_, live)| *live) {
+            let target = self.compile(rule.target)?;
+            next = Some(match (&rule.condition, next) {
+                (None, _) | (_, None) => target,
+                (Some(condition), Some(next)) => {
+                    let attribute = self
+                        .universe
+                        .position(condition.key)
+                        .ok_or(AoCError::from("invalid attribute"))?;
+                    let (threshold, low, high) = if condition.less {
+                        (condition.value, target, next)
                     } else {
-                        left = RangeInclusive::new(*range.start(), condition.value);
-                        right = RangeInclusive::new(condition.value + 1, *range.end());
-                    }
-                    for range in [left, right] {
-                        if range.is_empty() {
-                            continue;
-                        }
-                        let new_ranges = ranges
-                            .iter()
-                            .map(|(&n, r)| {
-                                if n == condition.key {
-                                    (n, range.clone())
-                                } else {
-                                    (n, r.clone())
-                                }
-                            })
-                            .collect::<BTreeMap<_, _>>();
-                        if condition.check_point(*range.start()) {
-                            stack.push((next_workflow, new_ranges));
-                        } else {
-                            ranges = new_ranges;
-                        }
-                    }
-                } else {
-                    if condition.check_point(*range.start()) {
-                        stack.push((next_workflow, ranges.clone()));
-                        break;
+                        (condition.value + 1, next, target)
+                    };
+                    if low == high {
+                        low
+                    } else {
+                        self.nodes.push(Node::Split {
+                            attribute,
+                            threshold,
+                            low,
+                            high,
+                        });
+                        self.nodes.len() - 1
                     }
                 }
+            });
+        }
+        let entry = next.ok_or(AoCError::from(format!("{} has no rules", name)))?;
+        self.entries.insert(name, Some(entry));
+        Ok(entry)
+    }
+}
+
+impl Universe<'_> {
+    fn position(&self, attribute: &str) -> Option<usize> {
+        self.attributes.iter().position(|&a| a == attribute)
+    }
+}
+
+impl core::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error: {}", self.message),
+            Severity::Warning => write!(f, "warning: {}", self.message),
+        }
+    }
+}
+
+fn check(workflows: &Workflows, universe: &Universe) -> Result<(), AoCError> {
+    let errors = analyze(workflows, universe)
+        .into_iter()
+        .filter(|d| d.severity == Severity::Error)
+        .map(|d| d.message)
+        .collect::<Vec<String>>();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AoCError::from(errors.join("; ")))
+    }
+}
+
+fn analyze(workflows: &Workflows, universe: &Universe) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut report = |severity, message| diagnostics.push(Diagnostic { severity, message });
+    let mut edges = BTreeMap::<&str, Vec<&str>>::new();
+    for (&name, rules) in workflows {
+        let mut live = Vec::new();
+        let live_rules = liveness(rules, universe);
+        for (i, (rule, matched)) in rules.iter().zip(live_rules).enumerate() {
+            if let Some(condition) = rule.condition.as_ref().filter(|c| {
+                universe.known > 0
+                    && universe
+                        .position(c.key)
+                        .is_some_and(|a| a >= universe.known)
+            }) {
+                report(
+                    Severity::Error,
+                    format!(
+                        "rule {} of {} uses attribute {} that no part has",
+                        i + 1,
+                        name,
+                        condition.key
+                    ),
+                );
+            }
+            if !matches!(rule.target, "A" | "R") && !workflows.contains_key(rule.target) {
+                report(
+                    Severity::Error,
+                    format!(
+                        "rule {} of {} sends parts to undefined workflow {}",
+                        i + 1,
+                        name,
+                        rule.target
+                    ),
+                );
+            }
+            if matched {
+                live.push(rule.target);
             } else {
-                stack.push((next_workflow, ranges.clone()));
+                report(
+                    Severity::Warning,
+                    format!("rule {} of {} is never applied", i + 1, name),
+                );
             }
         }
+        if rules.last().is_some_and(|rule| rule.condition.is_some()) {
+            report(Severity::Error, format!("{} has no fallback rule", name));
+        }
+        edges.insert(name, live);
     }
-    Ok(answer)
+
+    if workflows.contains_key("in") {
+        let mut reached = BTreeSet::from(["in"]);
+        let mut stack = Vec::from(["in"]);
+        while let Some(name) = stack.pop() {
+            for &next in edges.get(name).into_iter().flatten() {
+                if edges.contains_key(next) && reached.insert(next) {
+                    stack.push(next);
+                }
+            }
+        }
+        for name in edges.keys().filter(|name| !reached.contains(*name)) {
+            report(
+                Severity::Warning,
+                format!("workflow {} is unreachable", name),
+            );
+        }
+    } else {
+        report(Severity::Error, String::from("no in workflow"));
+    }
+
+    let mut done = BTreeSet::new();
+    for &name in edges.keys() {
+        let mut path = Vec::new();
+        find_cycles(name, &edges, &mut path, &mut done, &mut report);
+    }
+    diagnostics
+}
+
+fn liveness(rules: &[Rule], universe: &Universe) -> Vec<bool> {
+    let mut remaining = universe.bounds.clone();
+    rules
+        .iter()
+        .map(|rule| {
+            let Some(condition) = &rule.condition else {
+                let matched = remaining.iter().all(|&(low, high)| low <= high);
+                remaining.iter_mut().for_each(|range| *range = (1, 0));
+                return matched;
+            };
+            let Some(attribute) = universe.position(condition.key) else {
+                return true;
+            };
+            let (low, high) = remaining[attribute];
+            let (matched, rest) = if condition.less {
+                (
+                    (low, high.min(condition.value - 1)),
+                    (low.max(condition.value), high),
+                )
+            } else {
+                (
+                    (low.max(condition.value + 1), high),
+                    (low, high.min(condition.value)),
+                )
+            };
+            let others = remaining
+                .iter()
+                .enumerate()
+                .all(|(a, &(low, high))| a == attribute || low <= high);
+            remaining[attribute] = rest;
+            matched.0 <= matched.1 && others
+        })
+        .collect()
+}
+
+fn find_cycles<'a, F: FnMut(Severity, String)>(
+    name: &'a str,
+    edges: &BTreeMap<&'a str, Vec<&'a str>>,
+    path: &mut Vec<&'a str>,
+    done: &mut BTreeSet<&'a str>,
+    report: &mut F,
+) {
+    if done.contains(name) {
+        return;
+    }
+    if let Some(pos) = path.iter().position(|&n| n == name) {
+        let cycle = path[pos..]
+            .iter()
+            .chain([&name])
+            .copied()
+            .collect::<Vec<&str>>();
+        report(Severity::Error, format!("cycle {}", cycle.join(" -> ")));
+        return;
+    }
+    let Some(next) = edges.get(name) else {
+        return;
+    };
+    path.push(name);
+    for &next in next {
+        find_cycles(next, edges, path, done, report);
+    }
+    path.pop();
+    done.insert(name);
+}
+
+fn partition(workflows: &Workflows, universe: &Universe) -> Result<Vec<(Cuboid, bool)>, AoCError> {
+    let mut compiler = Compiler {
+        workflows,
+        universe,
+        nodes: Vec::from([Node::Accept, Node::Reject]),
+        entries: BTreeMap::new(),
+    };
+    let root = compiler.compile("in")?;
+    let nodes = compiler.nodes;
+
+    let mut boxes = Vec::new();
+    let mut stack = Vec::from([(root, universe.bounds.clone())]);
+    while let Some((node, cuboid)) = stack.pop() {
+        match nodes[node] {
+            Node::Accept => boxes.push((cuboid, true)),
+            Node::Reject => boxes.push((cuboid, false)),
+            Node::Split {
+                attribute,
+                threshold,
+                low,
+                high,
+            } => {
+                let (from, to) = cuboid[attribute];
+                for (next, range) in [
+                    (high, (threshold.max(from), to)),
+                    (low, (from, (threshold - 1).min(to))),
+                ] {
+                    if range.0 <= range.1 {
+                        let mut cuboid = cuboid.clone();
+                        cuboid[attribute] = range;
+                        stack.push((next, cuboid));
+                    }
+                }
+            }
+        }
+    }
+    Ok(boxes)
+}
+
+fn volume(cuboid: &Cuboid) -> Option<u128> {
+    cuboid.iter().try_fold(1u128, |volume, (low, high)| {
+        volume.checked_mul((*high as i128 - *low as i128 + 1) as u128)
+    })
 }
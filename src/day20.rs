@@ -1,14 +1,19 @@
 use crate::error::AoCError;
-use crate::utils::lcm;
+use crate::utils::{lcm, parse_options};
 use alloc::boxed::Box;
 use alloc::collections::{BTreeMap, VecDeque};
+use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
+use core::fmt;
+
+const OPTIONS: &[&str] = &["presses", "until", "limit", "restore"];
 
 trait Module<'a> {
     fn tick(&mut self, pulse: bool, from: &'a str) -> Option<bool>;
     fn connect(&mut self, from: &'a str);
-    fn reset(&mut self);
+    fn state(&self) -> String;
+    fn restore(&mut self, state: &str) -> Result<(), AoCError>;
 }
 
 struct FlipFlop(bool);
@@ -22,8 +27,17 @@ impl<'a> Module<'a> for FlipFlop {
 
     fn connect(&mut self, _: &str) {}
 
-    fn reset(&mut self) {
-        self.0 = false;
+    fn state(&self) -> String {
+        String::from(if self.0 { "=1" } else { "=0" })
+    }
+
+    fn restore(&mut self, state: &str) -> Result<(), AoCError> {
+        self.0 = match state {
+            "=0" => false,
+            "=1" => true,
+            _ => return Err(AoCError::from("flip-flop state should be =0 or =1")),
+        };
+        Ok(())
     }
 }
 
@@ -38,8 +52,37 @@ impl<'a> Module<'a> for Conjunction<'a> {
         self.0.insert(from, false);
     }
 
-    fn reset(&mut self) {
-        self.0.values_mut().for_each(|v| *v = false);
+    fn state(&self) -> String {
+        let inputs = self
+            .0
+            .iter()
+            .map(|(name, &high)| format!("{}={}", name, high as u8))
+            .collect::<Vec<String>>();
+        format!("[{}]", inputs.join(","))
+    }
+
+    fn restore(&mut self, state: &str) -> Result<(), AoCError> {
+        let inputs = state
+            .strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .ok_or(AoCError::from(
+                "conjunction state should be [input=bit,...]",
+            ))?;
+        for input in inputs.split(',').filter(|input| !input.is_empty()) {
+            let (name, bit) = input.split_once('=').ok_or(AoCError::from(
+                "conjunction state should be [input=bit,...]",
+            ))?;
+            let memory = self
+                .0
+                .get_mut(name)
+                .ok_or(AoCError::from(format!("unknown input {}", name)))?;
+            *memory = match bit {
+                "0" => false,
+                "1" => true,
+                _ => return Err(AoCError::from("input state should be 0 or 1")),
+            };
+        }
+        Ok(())
     }
 }
 
@@ -51,15 +94,35 @@ impl<'a> Module<'a> for Broadcaster {
 
     fn connect(&mut self, _: &str) {}
 
-    fn reset(&mut self) {}
+    fn state(&self) -> String {
+        String::new()
+    }
+
+    fn restore(&mut self, state: &str) -> Result<(), AoCError> {
+        match state {
+            "" => Ok(()),
+            _ => Err(AoCError::from("broadcaster has no state")),
+        }
+    }
+}
+
+struct Network<'a> {
+    modules: BTreeMap<&'a str, Box<dyn Module<'a> + 'a>>,
+    wires: BTreeMap<&'a str, Vec<&'a str>>,
+    presses: u64,
+}
+
+struct Snapshot<'a> {
+    presses: u64,
+    states: Vec<(&'a str, String)>,
 }
 
 pub(crate) fn part1(input: String) -> Result<String, AoCError> {
-    let (mut modules, wires) = parse_input(&input)?;
+    let mut network = parse_network(&input)?;
 
     let mut counts = [0, 0];
     for _ in 0..1000 {
-        push_button(&mut modules, &wires, |_, pulse| {
+        network.push_button(|_, _, pulse| {
             counts[pulse as usize] += 1;
         });
     }
@@ -68,47 +131,112 @@ pub(crate) fn part1(input: String) -> Result<String, AoCError> {
 }
 
 pub(crate) fn part2(input: String) -> Result<String, AoCError> {
-    let (mut modules, wires) = parse_input(&input)?;
-    let prev = wires
+    let mut network = parse_network(&input)?;
+    let prev = network
+        .wires
         .iter()
         .find_map(|(&name, links)| links.contains(&"rx").then_some(name))
         .ok_or(AoCError::from("not solvable"))?;
-    let branches = wires
+    let branches = network
+        .wires
         .iter()
         .filter_map(|(&name, links)| links.contains(&prev).then_some(name))
         .collect::<Vec<_>>();
+    let initial = network.snapshot();
     let values = branches
         .iter()
         .map(|&name| {
-            modules.values_mut().for_each(|m| m.reset());
-            let mut answer = 0u64;
-            loop {
-                answer += 1;
-                let mut found = false;
-                push_button(&mut modules, &wires, |from, pulse| {
-                    if pulse && from == name {
-                        found = true;
-                    }
-                });
-                if found {
-                    break;
-                }
-            }
-            answer
+            network.restore(&initial)?;
+            network
+                .run_until(u64::MAX, |from, _, pulse| pulse && from == name)
+                .ok_or(AoCError::from("never sends a high pulse"))
         })
-        .collect::<Vec<_>>();
+        .collect::<Result<Vec<_>, AoCError>>()?;
     Ok(values.into_iter().fold(1, lcm).to_string())
 }
 
-fn parse_input(
-    input: &str,
-) -> Result<
-    (
-        BTreeMap<&str, Box<dyn Module + '_>>,
-        BTreeMap<&str, Vec<&str>>,
-    ),
-    AoCError,
-> {
+pub(crate) fn details(input: String) -> Result<String, AoCError> {
+    let (options, _) = parse_options(&input, OPTIONS)?;
+    let presses = options.get("presses").map_or(Ok(1), |v| v.parse::<u64>())?;
+    let limit = options
+        .get("limit")
+        .map_or(Ok(100000), |v| v.parse::<u64>())?;
+    let mut network = parse_network(&input)?;
+    let initial = network.snapshot();
+
+    let mut lines = Vec::new();
+    for _ in 0..presses {
+        network.push_button(|from, to, pulse| lines.push(describe(from, to, pulse)));
+        lines.push(String::new());
+    }
+    lines.push(network.snapshot().to_string());
+
+    if let Some(until) = options.get("until") {
+        let (from, pulse, to) = parse_pattern(until)?;
+        network.restore(&initial)?;
+        let found = network.run_until(limit, |f, t, p| {
+            (from == "*" || from == f) && (to == "*" || to == t) && pulse.is_none_or(|v| v == p)
+        });
+        lines.push(String::new());
+        match found {
+            Some(press) => {
+                lines.push(format!("{} first happens during press {}", until, press));
+                lines.push(network.snapshot().to_string());
+            }
+            None => lines.push(format!(
+                "{} does not happen within {} presses",
+                until, limit
+            )),
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+fn describe(from: &str, to: &str, pulse: bool) -> String {
+    format!("{} -{}-> {}", from, if pulse { "high" } else { "low" }, to)
+}
+
+fn parse_pattern(pattern: &str) -> Result<(&str, Option<bool>, &str), AoCError> {
+    let (from, rest) = pattern
+        .split_once(" -")
+        .ok_or(AoCError::from("pattern should look like a -high-> b"))?;
+    let (pulse, to) = rest
+        .split_once("-> ")
+        .ok_or(AoCError::from("pattern should look like a -high-> b"))?;
+    let pulse = match pulse {
+        "low" => Some(false),
+        "high" => Some(true),
+        "*" => None,
+        _ => return Err(AoCError::from("unknown pulse")),
+    };
+    Ok((from, pulse, to))
+}
+
+fn parse_snapshot(snapshot: &str) -> Result<Snapshot<'_>, AoCError> {
+    let (presses, states) = snapshot
+        .strip_prefix("after ")
+        .and_then(|s| s.split_once(" presses:"))
+        .ok_or(AoCError::from(
+            "snapshot should start with after N presses:",
+        ))?;
+    let states = states
+        .split_whitespace()
+        .map(|module| {
+            let split = module
+                .find(['=', '['])
+                .ok_or(AoCError::from(format!("invalid module state {}", module)))?;
+            let (name, state) = module.split_at(split);
+            Ok((name, state.to_string()))
+        })
+        .collect::<Result<Vec<(&str, String)>, AoCError>>()?;
+    Ok(Snapshot {
+        presses: presses.parse::<u64>()?,
+        states,
+    })
+}
+
+fn parse_network(input: &str) -> Result<Network<'_>, AoCError> {
+    let (options, input) = parse_options(input, OPTIONS)?;
     let mut modules = BTreeMap::<&str, Box<dyn Module>>::new();
     let mut wires = BTreeMap::<&str, Vec<&str>>::new();
 
@@ -145,26 +273,80 @@ fn parse_input(
         });
     });
 
-    Ok((modules, wires))
+    let mut network = Network {
+        modules,
+        wires,
+        presses: 0,
+    };
+    if let Some(snapshot) = options.get("restore") {
+        network.restore(&parse_snapshot(snapshot)?)?;
+    }
+    Ok(network)
 }
 
-fn push_button<'a, F: FnMut(&str, bool)>(
-    modules: &mut BTreeMap<&'a str, Box<(dyn Module<'a> + 'a)>>,
-    wires: &BTreeMap<&'a str, Vec<&'a str>>,
-    mut callback: F,
-) {
-    let mut queue = VecDeque::from([("button", "broadcaster", false)]);
-    while let Some((from, to, pulse)) = queue.pop_front() {
-        callback(from, pulse);
-
-        if let Some(module) = modules.get_mut(to) {
-            if let Some(pulse) = module.tick(pulse, from) {
-                if let Some(links) = wires.get(to) {
-                    links.iter().for_each(|link| {
-                        queue.push_back((to, link, pulse));
-                    });
+impl<'a> Network<'a> {
+    fn push_button<F: FnMut(&'a str, &'a str, bool)>(&mut self, mut callback: F) {
+        self.presses += 1;
+        let mut queue = VecDeque::from([("button", "broadcaster", false)]);
+        while let Some((from, to, pulse)) = queue.pop_front() {
+            callback(from, to, pulse);
+
+            if let Some(module) = self.modules.get_mut(to) {
+                if let Some(pulse) = module.tick(pulse, from) {
+                    if let Some(links) = self.wires.get(to) {
+                        links.iter().for_each(|link| {
+                            queue.push_back((to, link, pulse));
+                        });
+                    }
                 }
             }
         }
     }
+
+    fn run_until<P: FnMut(&str, &str, bool) -> bool>(
+        &mut self,
+        limit: u64,
+        mut predicate: P,
+    ) -> Option<u64> {
+        for _ in 0..limit {
+            let mut found = false;
+            self.push_button(|from, to, pulse| found |= predicate(from, to, pulse));
+            if found {
+                return Some(self.presses);
+            }
+        }
+        None
+    }
+
+    fn snapshot(&self) -> Snapshot<'a> {
+        Snapshot {
+            presses: self.presses,
+            states: self
+                .modules
+                .iter()
+                .map(|(&name, module)| (name, module.state()))
+                .collect(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: &Snapshot<'a>) -> Result<(), AoCError> {
+        self.presses = snapshot.presses;
+        for (name, state) in &snapshot.states {
+            self.modules
+                .get_mut(name)
+                .ok_or(AoCError::from(format!("unknown module {}", name)))?
+                .restore(state)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Snapshot<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "after {} presses:", self.presses)?;
+        for (name, state) in self.states.iter().filter(|(_, state)| !state.is_empty()) {
+            write!(f, " {}{}", name, state)?;
+        }
+        Ok(())
+    }
 }
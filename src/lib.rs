@@ -36,7 +36,9 @@ use alloc::boxed::Box;
 use alloc::format;
 use alloc::string::String;
 
-static SOLUTIONS: &[fn(String) -> Result<String, AoCError>] = &[
+type Solution = fn(String) -> Result<String, AoCError>;
+
+static SOLUTIONS: &[Solution] = &[
     day01::part1,
     day01::part2,
     day02::part1,
@@ -89,6 +91,21 @@ static SOLUTIONS: &[fn(String) -> Result<String, AoCError>] = &[
     day25::part2,
 ];
 
+static DETAILS: &[(usize, Solution)] = &[
+    (5, day06::details),
+    (7, day08::details),
+    (9, day10::details),
+    (10, day11::details),
+    (11, day12::details),
+    (12, day13::details),
+    (14, day15::details),
+    (15, day16::details),
+    (16, day17::details),
+    (17, day18::details),
+    (18, day19::details),
+    (19, day20::details),
+];
+
 #[repr(C, packed)]
 struct JSString {
     data: *mut u8,
@@ -97,9 +114,21 @@ struct JSString {
 
 #[no_mangle]
 extern "C" fn solve(index: usize, str: *mut JSString) -> bool {
+    run(SOLUTIONS.get(index).copied(), index, str)
+}
+
+#[no_mangle]
+extern "C" fn details(index: usize, str: *mut JSString) -> bool {
+    let f = DETAILS
+        .iter()
+        .find_map(|&(day, f)| (day == index).then_some(f))
+        .unwrap_or(|_| Ok(String::new()));
+    run(Some(f), index, str)
+}
+
+fn run(f: Option<Solution>, index: usize, str: *mut JSString) -> bool {
     let input = unsafe { String::from_raw_parts((*str).data, (*str).len, (*str).len) };
-    let (ok, output) = SOLUTIONS
-        .get(index)
+    let (ok, output) = f
         .map_or(Err(format!("Invalid index {}", index).into()), |f| f(input))
         .map_or_else(|e| (false, String::from(e)), |r| (true, r));
     unsafe {
@@ -1,3 +1,7 @@
+use crate::error::AoCError;
+use alloc::collections::BTreeMap;
+use alloc::format;
+
 fn gcd(x: u64, y: u64) -> u64 {
     let z = x | y;
     if x == 0 || y == 0 {
@@ -19,3 +23,28 @@ fn gcd(x: u64, y: u64) -> u64 {
 pub(crate) fn lcm(x: u64, y: u64) -> u64 {
     x / gcd(x, y) * y
 }
+
+pub(crate) fn parse_options<'a>(
+    input: &'a str,
+    known: &[&str],
+) -> Result<(BTreeMap<&'a str, &'a str>, &'a str), AoCError> {
+    let input = input.trim();
+    let (header, rest) = input.split_once("\n\n").unwrap_or((input, ""));
+    let options = header
+        .lines()
+        .map(|line| {
+            line.split_once(": ").filter(|(key, _)| {
+                !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            })
+        })
+        .collect::<Option<BTreeMap<&str, &str>>>();
+    match options {
+        Some(options) => {
+            if let Some(key) = options.keys().find(|key| !known.contains(key)) {
+                return Err(AoCError::from(format!("unknown option {}", key)));
+            }
+            Ok((options, rest))
+        }
+        None => Ok((BTreeMap::new(), input)),
+    }
+}
@@ -1,12 +1,13 @@
 const worker1 = new Worker("worker.js");
 const worker2 = new Worker("worker.js");
+const worker3 = new Worker("worker.js");
 
-function solveAsync(worker, index, input) {
+function solveAsync(worker, index, input, method = "solve") {
     return new Promise((resolve, reject) => {
         worker.onmessage = e => {
             (e.data[0] ? resolve : reject)(e.data[1]);
         };
-        worker.postMessage([index, input]);
+        worker.postMessage([method, index, input]);
     });
 }
 
@@ -24,19 +25,41 @@ function handleOutput(p, e) {
     });
 }
 
+function handleDetails(p, e) {
+    return p.then(output => {
+        if (output.startsWith("<svg")) {
+            const image = document.createElement("img");
+            image.src = "data:image/svg+xml;charset=utf-8," + encodeURIComponent(output);
+            e.replaceChildren(image);
+        } else {
+            e.textContent = output;
+        }
+        e.classList.toggle("hidden", output.length === 0);
+    }).catch(reason => {
+        e.textContent = reason;
+        e.classList.add("error");
+        e.classList.remove("hidden");
+    });
+}
+
 function exec() {
     const output1 = document.getElementById("output1");
     const output2 = document.getElementById("output2");
+    const details = document.getElementById("details");
     const solveBtn = document.getElementById("solve");
     prepareOutput(output1);
     prepareOutput(output2);
+    details.textContent = "";
+    details.classList.remove("error");
+    details.classList.add("hidden");
     solveBtn.classList.add("inProgress");
     document.body.style.cursor = "wait";
     const day = parseInt(document.getElementById("day").value) - 1;
     const input = document.getElementById("input").value;
     const part1 = handleOutput(solveAsync(worker1, 2 * day, input), output1);
     const part2 = handleOutput(solveAsync(worker2, 2 * day + 1, input), output2);
-    return Promise.all([part1, part2]).finally(() => {
+    const extra = handleDetails(solveAsync(worker3, day, input, "details"), details);
+    return Promise.all([part1, part2, extra]).finally(() => {
         solveBtn.classList.remove("inProgress");
         document.body.style.cursor = "default";
     });
@@ -4,12 +4,12 @@ const decoder = new TextDecoder();
 
 onmessage = async (e) => {
     const wasm = await wasmPromise;
-    const buf = encoder.encode(e.data[1]);
+    const buf = encoder.encode(e.data[2]);
     const str_ptr = wasm.exports.alloc(buf.length);
     new Uint8Array(wasm.exports.memory.buffer, str_ptr, buf.length).set(buf);
     const parts_ptr = wasm.exports.alloc(8);
     new Uint32Array(wasm.exports.memory.buffer, parts_ptr, 2).set([str_ptr, buf.length]);
-    const ok = wasm.exports.solve(e.data[0], parts_ptr);
+    const ok = wasm.exports[e.data[0]](e.data[1], parts_ptr);
     const output_parts = new Uint32Array(wasm.exports.memory.buffer, parts_ptr, 2);
     const output_buf = new Uint8Array(wasm.exports.memory.buffer, output_parts[0], output_parts[1]);
     const output = decoder.decode(output_buf);